@@ -4,10 +4,10 @@ use spritebot_storage::Sprite;
 use vfs::PhysicalFS;
 
 fn main() {
-    let sprite_path = args().skip(1).next().unwrap();
-    let mut vfs = PhysicalFS::new(&sprite_path);
+    let sprite_path = args().nth(1).unwrap();
+    let vfs = PhysicalFS::new(&sprite_path);
 
-    let mut sprite = Sprite::new(&mut vfs).unwrap();
+    let mut sprite = Sprite::new(&vfs).unwrap();
 
     sprite.animations[0].images[7][0].offsets.center = (100, 100);
 
@@ -0,0 +1,177 @@
+use std::fmt::Display;
+
+/// The kind of an animation, as identified by its name in AnimData.xml.
+///
+/// The standard kinds are the actions SpriteBot knows about. Any other name is kept as [`AnimationKind::Other`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AnimationKind {
+    Walk,
+    Attack,
+    Kick,
+    Shoot,
+    Strike,
+    Sleep,
+    Hurt,
+    Idle,
+    Swing,
+    Double,
+    Hop,
+    Charge,
+    Rotate,
+    EventSleep,
+    Wake,
+    Eat,
+    Tumble,
+    Pose,
+    Pull,
+    Pain,
+    Float,
+    DeepBreath,
+    Nod,
+    Sit,
+    LookUp,
+    Sink,
+    Trip,
+    Laying,
+    LeapForth,
+    Head,
+    Cringe,
+    LostBalance,
+    TumbleBack,
+    Faint,
+    HitGround,
+    /// An animation with a name that isn’t one of the standard one. [`AnimationKind::from_name`] never return it for a standard name, but one built by hand with a standard name still refer to that standard animation (except for `==`, which compare the variants).
+    Other(String),
+}
+
+/// The standard animations with their name. Their position is their canonical index.
+const STANDARD_ANIMATIONS: [(AnimationKind, &str); 35] = [
+    (AnimationKind::Walk, "Walk"),
+    (AnimationKind::Attack, "Attack"),
+    (AnimationKind::Kick, "Kick"),
+    (AnimationKind::Shoot, "Shoot"),
+    (AnimationKind::Strike, "Strike"),
+    (AnimationKind::Sleep, "Sleep"),
+    (AnimationKind::Hurt, "Hurt"),
+    (AnimationKind::Idle, "Idle"),
+    (AnimationKind::Swing, "Swing"),
+    (AnimationKind::Double, "Double"),
+    (AnimationKind::Hop, "Hop"),
+    (AnimationKind::Charge, "Charge"),
+    (AnimationKind::Rotate, "Rotate"),
+    (AnimationKind::EventSleep, "EventSleep"),
+    (AnimationKind::Wake, "Wake"),
+    (AnimationKind::Eat, "Eat"),
+    (AnimationKind::Tumble, "Tumble"),
+    (AnimationKind::Pose, "Pose"),
+    (AnimationKind::Pull, "Pull"),
+    (AnimationKind::Pain, "Pain"),
+    (AnimationKind::Float, "Float"),
+    (AnimationKind::DeepBreath, "DeepBreath"),
+    (AnimationKind::Nod, "Nod"),
+    (AnimationKind::Sit, "Sit"),
+    (AnimationKind::LookUp, "LookUp"),
+    (AnimationKind::Sink, "Sink"),
+    (AnimationKind::Trip, "Trip"),
+    (AnimationKind::Laying, "Laying"),
+    (AnimationKind::LeapForth, "LeapForth"),
+    (AnimationKind::Head, "Head"),
+    (AnimationKind::Cringe, "Cringe"),
+    (AnimationKind::LostBalance, "LostBalance"),
+    (AnimationKind::TumbleBack, "TumbleBack"),
+    (AnimationKind::Faint, "Faint"),
+    (AnimationKind::HitGround, "HitGround"),
+];
+
+impl AnimationKind {
+    /// Get the kind of the animation with the given name. Names are case sensitive, like in SpriteBot.
+    pub fn from_name(name: &str) -> Self {
+        STANDARD_ANIMATIONS
+            .iter()
+            .find(|(_, standard_name)| *standard_name == name)
+            .map(|(kind, _)| kind.clone())
+            .unwrap_or_else(|| Self::Other(name.to_string()))
+    }
+
+    /// Get the standard animation with the given canonical index
+    pub fn from_index(index: u32) -> Option<Self> {
+        STANDARD_ANIMATIONS
+            .get(index as usize)
+            .map(|(kind, _)| kind.clone())
+    }
+
+    /// The name of the animation, as used in AnimData.xml and in the sheet file names
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Other(name) => name,
+            standard => STANDARD_ANIMATIONS
+                .iter()
+                .find(|(kind, _)| kind == standard)
+                .map(|(_, name)| *name)
+                .unwrap(), // All the other variants are in STANDARD_ANIMATIONS
+        }
+    }
+
+    /// The canonical index of the animation, or `None` if it isn’t a standard one. An [`AnimationKind::Other`] with a standard name has the index of that standard animation.
+    pub fn index(&self) -> Option<u32> {
+        let name = self.name();
+        STANDARD_ANIMATIONS
+            .iter()
+            .position(|(_, standard_name)| *standard_name == name)
+            .map(|position| position as u32)
+    }
+
+    /// Iterate over all the standard kinds, in the order of their index
+    pub fn standard_kinds() -> impl Iterator<Item = AnimationKind> {
+        STANDARD_ANIMATIONS.iter().map(|(kind, _)| kind.clone())
+    }
+}
+
+impl Display for AnimationKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl From<&str> for AnimationKind {
+    fn from(name: &str) -> Self {
+        Self::from_name(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AnimationKind;
+
+    #[test]
+    fn names_and_indices_are_inverses() {
+        let mut count = 0;
+        for (expected_index, kind) in AnimationKind::standard_kinds().enumerate() {
+            assert_eq!(AnimationKind::from_name(kind.name()), kind);
+            assert_eq!(kind.index(), Some(expected_index as u32));
+            assert_eq!(AnimationKind::from_index(expected_index as u32), Some(kind));
+            count += 1;
+        }
+        assert_eq!(count, 35);
+        assert_eq!(AnimationKind::from_index(35), None);
+        assert_eq!(AnimationKind::Walk.index(), Some(0));
+        assert_eq!(AnimationKind::Idle.index(), Some(7));
+    }
+
+    #[test]
+    fn other_names_are_kept() {
+        let custom = AnimationKind::from_name("Cutscene");
+        assert_eq!(custom, AnimationKind::Other("Cutscene".to_string()));
+        assert_eq!(custom.name(), "Cutscene");
+        assert_eq!(custom.index(), None);
+        // Names are case sensitive
+        assert_eq!(
+            AnimationKind::from_name("walk"),
+            AnimationKind::Other("walk".to_string())
+        );
+        // An `Other` with a standard name is normalised by `from_name`, and has its index
+        let walk = AnimationKind::Other("Walk".to_string());
+        assert_eq!(AnimationKind::from_name(walk.name()), AnimationKind::Walk);
+        assert_eq!(walk.index(), Some(0));
+    }
+}
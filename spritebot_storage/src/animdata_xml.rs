@@ -1,38 +1,673 @@
-use serde::{Deserialize, Serialize};
+use std::{io::BufRead, str::FromStr};
 
-#[derive(Deserialize, Serialize, Debug)]
-#[serde(rename_all = "PascalCase")]
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+
+use crate::SpriteBotStorageError;
+
+/// A minimal XML tree, enough to represent AnimData.xml without losing any of its content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct XmlElement {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<XmlNode>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum XmlNode {
+    Element(XmlElement),
+    Text(String),
+    Comment(String),
+}
+
+impl XmlElement {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            attributes: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    /// The concatenation of all the text directly inside this element, with the surrounding whitespace removed
+    fn text(&self) -> String {
+        let mut result = String::new();
+        for child in &self.children {
+            if let XmlNode::Text(text) = child {
+                result.push_str(text);
+            }
+        }
+        result.trim().to_string()
+    }
+
+    /// Parse the text of the element, which is the known element `element` of the animation `animation` if any
+    fn parse_text<T: FromStr>(
+        &self,
+        element: &'static str,
+        animation: Option<&str>,
+    ) -> Result<T, SpriteBotStorageError> {
+        let text = self.text();
+        text.parse()
+            .map_err(|_| SpriteBotStorageError::XmlInvalidValue {
+                element,
+                animation: animation.map(str::to_string),
+                value: text,
+            })
+    }
+
+    /// Write the element the same way SpriteBot does (it uses Python’s `minidom` `toprettyxml` with tab indentation): one node per line, except for elements that only contain a text.
+    fn write_to(&self, out: &mut String, indentation: &str) {
+        out.push_str(indentation);
+        out.push('<');
+        out.push_str(&self.name);
+        for (key, value) in &self.attributes {
+            out.push(' ');
+            out.push_str(key);
+            out.push_str("=\"");
+            push_escaped(out, value);
+            out.push('"');
+        }
+        if self.children.is_empty() {
+            out.push_str("/>\n");
+            return;
+        }
+        out.push('>');
+        if let [XmlNode::Text(text)] = self.children.as_slice() {
+            push_escaped(out, text);
+        } else {
+            out.push('\n');
+            let child_indentation = format!("{}\t", indentation);
+            for child in &self.children {
+                match child {
+                    XmlNode::Element(element) => element.write_to(out, &child_indentation),
+                    XmlNode::Text(text) => {
+                        out.push_str(&child_indentation);
+                        push_escaped(out, text);
+                        out.push('\n');
+                    }
+                    XmlNode::Comment(comment) => {
+                        out.push_str(&child_indentation);
+                        out.push_str("<!--");
+                        out.push_str(comment);
+                        out.push_str("-->\n");
+                    }
+                }
+            }
+            out.push_str(indentation);
+        }
+        out.push_str("</");
+        out.push_str(&self.name);
+        out.push_str(">\n");
+    }
+}
+
+/// Escape text the way `minidom` does. Unlike [`quick_xml::escape::escape`], it doesn’t escape `'`.
+fn push_escaped(out: &mut String, text: &str) {
+    for character in text.chars() {
+        match character {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            other => out.push(other),
+        }
+    }
+}
+
+fn element_from_start<R>(
+    reader: &Reader<R>,
+    start: &BytesStart,
+) -> Result<XmlElement, quick_xml::Error> {
+    let mut element = XmlElement::new(&reader.decoder().decode(start.name().as_ref())?);
+    for attribute in start.attributes() {
+        let attribute = attribute?;
+        element.attributes.push((
+            reader.decoder().decode(attribute.key.as_ref())?.to_string(),
+            attribute.unescape_value()?.to_string(),
+        ));
+    }
+    Ok(element)
+}
+
+/// Parse a whole XML document, returning its root element.
+///
+/// Text made only of whitespace is ignored, as it is just formatting. So is everything outside of the root element.
+fn parse_document<R: BufRead>(source: R) -> Result<XmlElement, SpriteBotStorageError> {
+    let mut reader = Reader::from_reader(source);
+    let mut buf = Vec::new();
+    let mut stack: Vec<XmlElement> = Vec::new();
+    let mut root = None;
+
+    loop {
+        let finished_element = match reader.read_event_into(&mut buf)? {
+            Event::Start(start) => {
+                stack.push(element_from_start(&reader, &start)?);
+                None
+            }
+            Event::Empty(start) => Some(element_from_start(&reader, &start)?),
+            // quick_xml already check the end tag match the last start tag
+            Event::End(_) => stack.pop(),
+            Event::Text(text) => {
+                let text = text.unescape()?;
+                if let Some(parent) = stack.last_mut() {
+                    if !text.trim().is_empty() {
+                        parent.children.push(XmlNode::Text(text.to_string()));
+                    }
+                }
+                None
+            }
+            Event::CData(cdata) => {
+                let text = reader
+                    .decoder()
+                    .decode(&cdata)
+                    .map_err(quick_xml::Error::from)?;
+                if let Some(parent) = stack.last_mut() {
+                    parent.children.push(XmlNode::Text(text.to_string()));
+                }
+                None
+            }
+            Event::Comment(comment) => {
+                let comment = reader
+                    .decoder()
+                    .decode(&comment)
+                    .map_err(quick_xml::Error::from)?;
+                if let Some(parent) = stack.last_mut() {
+                    parent.children.push(XmlNode::Comment(comment.to_string()));
+                }
+                None
+            }
+            Event::Eof => break,
+            _ => None,
+        };
+        if let Some(element) = finished_element {
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(XmlNode::Element(element)),
+                None => root = Some(element),
+            }
+        }
+        buf.clear();
+    }
+
+    root.ok_or_else(|| SpriteBotStorageError::XmlMissingElement {
+        element: "AnimData",
+        animation: None,
+    })
+}
+
+/// Content of AnimData.xml this library doesn’t understand, kept so it can be written back.
+///
+/// It is opaque: it store the unknown attributes, elements and comments, along with where they are relative to the known elements, and the order of the known elements.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExtraMetadata {
+    attributes: Vec<(String, String)>,
+    /// The keys of the known elements, in the order they were read
+    order: Vec<String>,
+    /// Unknown nodes, with the key of the known element they follow (`None` if they are before all the known elements)
+    nodes: Vec<(Option<String>, XmlNode)>,
+    /// Extra metadata of the known child elements, by key
+    children: Vec<(String, ExtraMetadata)>,
+}
+
+static EMPTY_EXTRA_METADATA: ExtraMetadata = ExtraMetadata {
+    attributes: Vec::new(),
+    order: Vec::new(),
+    nodes: Vec::new(),
+    children: Vec::new(),
+};
+
+impl ExtraMetadata {
+    /// Return true if there is no unknown content at all
+    pub fn is_empty(&self) -> bool {
+        self.attributes.is_empty()
+            && self.nodes.is_empty()
+            && self.children.iter().all(|(_, child)| child.is_empty())
+    }
+
+    fn child(&self, key: &str) -> &ExtraMetadata {
+        self.children
+            .iter()
+            .find(|(child_key, _)| child_key == key)
+            .map(|(_, child)| child)
+            .unwrap_or(&EMPTY_EXTRA_METADATA)
+    }
+
+    /// Split the children of `element` between the known elements (those `known_key` return a key for) and the unknown nodes.
+    fn split(
+        element: XmlElement,
+        known_key: impl Fn(&XmlElement) -> Option<String>,
+    ) -> (Vec<(String, XmlElement)>, Self) {
+        let mut known = Vec::new();
+        let mut extra = Self {
+            attributes: element.attributes,
+            ..Default::default()
+        };
+        let mut last_key = None;
+        for child in element.children {
+            if let XmlNode::Element(child_element) = &child {
+                if let Some(key) = known_key(child_element) {
+                    if !extra.order.contains(&key) {
+                        extra.order.push(key.clone());
+                    }
+                    last_key = Some(key.clone());
+                    if let XmlNode::Element(child_element) = child {
+                        known.push((key, child_element));
+                    }
+                    continue;
+                }
+            }
+            extra.nodes.push((last_key.clone(), child));
+        }
+        (known, extra)
+    }
+
+    /// Record the unknown content of an element that only contain a value
+    fn from_value_element(element: &XmlElement) -> Self {
+        Self {
+            attributes: element.attributes.clone(),
+            order: Vec::new(),
+            nodes: element
+                .children
+                .iter()
+                .filter(|child| !matches!(child, XmlNode::Text(_)))
+                .map(|child| (None, child.clone()))
+                .collect(),
+            children: Vec::new(),
+        }
+    }
+
+    fn anchored_nodes<'a>(&'a self, key: Option<&'a String>) -> impl Iterator<Item = XmlNode> + 'a {
+        self.nodes
+            .iter()
+            .filter(move |(anchor, _)| anchor.as_ref() == key)
+            .map(|(_, node)| node.clone())
+    }
+
+    /// Sort the known elements (given in SpriteBot’s order) in the order they were read. Elements that weren’t read are put after all the elements that come before them in SpriteBot’s order.
+    fn in_source_order(&self, known: Vec<(String, XmlElement)>) -> Vec<(String, XmlElement)> {
+        let source_position = |key: &String| self.order.iter().position(|read| read == key);
+        let mut order = (0..known.len())
+            .filter(|position| source_position(&known[*position].0).is_some())
+            .collect::<Vec<_>>();
+        order.sort_by_key(|position| source_position(&known[*position].0));
+        for (position, (key, _)) in known.iter().enumerate() {
+            if source_position(key).is_none() {
+                // After every element that come before it in SpriteBot’s order
+                let insert_at = order
+                    .iter()
+                    .rposition(|other| *other < position)
+                    .map_or(0, |last| last + 1);
+                order.insert(insert_at, position);
+            }
+        }
+        let mut known = known.into_iter().map(Some).collect::<Vec<_>>();
+        order
+            .into_iter()
+            .map(|position| known[position].take().unwrap())
+            .collect()
+    }
+
+    /// Build back an element from its known children, putting the unknown content back in place.
+    ///
+    /// Unknown nodes that followed a known element that no longer exist are put at the end.
+    fn merge(&self, name: &str, known: Vec<(String, XmlElement)>) -> XmlElement {
+        let mut element = XmlElement::new(name);
+        element.attributes = self.attributes.clone();
+        element.children.extend(self.anchored_nodes(None));
+        for (position, (key, child)) in known.iter().enumerate() {
+            element.children.push(XmlNode::Element(child.clone()));
+            if known.get(position + 1).map(|(next_key, _)| next_key) != Some(key) {
+                element.children.extend(self.anchored_nodes(Some(key)));
+            }
+        }
+        for (anchor, node) in &self.nodes {
+            if let Some(anchor) = anchor {
+                if !known.iter().any(|(key, _)| key == anchor) {
+                    element.children.push(node.clone());
+                }
+            }
+        }
+        element
+    }
+
+    /// Build a known element containing only a value, with the extra metadata stored for the key `name`
+    fn value_element(&self, name: &str, value: String) -> (String, XmlElement) {
+        let mut element = self.child(name).merge(name, Vec::new());
+        element.children.insert(0, XmlNode::Text(value));
+        (name.to_string(), element)
+    }
+}
+
+#[derive(Debug)]
 pub struct AnimDataXML {
     pub shadow_size: u8,
     pub anims: AnimsXML,
+    pub extra: ExtraMetadata,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
-#[serde(rename_all = "PascalCase")]
+#[derive(Debug)]
 pub struct AnimsXML {
     pub anim: Vec<AnimXML>,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
-#[serde(rename_all = "PascalCase")]
+#[derive(Debug)]
 pub struct AnimXML {
-    //TODO: the server implementation seems to differ a bit to this.
-    //TODO: also, handle the CopyOf properly
     pub name: String,
     pub index: u32,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    /// When set, this animation reuses the sheets of the named animation, and has no frame data on its own.
+    pub copy_of: Option<String>,
     pub rush_frame: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub hit_frame: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub return_frame: Option<u32>,
-    pub frame_width: u32,
-    pub frame_height: u32,
-    pub durations: DurationsXML,
+    pub frame_width: Option<u32>,
+    pub frame_height: Option<u32>,
+    pub durations: Option<DurationsXML>,
+    pub extra: ExtraMetadata,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
-#[serde(rename_all = "PascalCase")]
+#[derive(Debug)]
 pub struct DurationsXML {
     pub duration: Vec<usize>,
 }
+
+const ANIMDATA_ELEMENTS: [&str; 2] = ["ShadowSize", "Anims"];
+
+/// The elements of an animation this library understand
+const ANIM_ELEMENTS: [&str; 9] = [
+    "Name",
+    "Index",
+    "CopyOf",
+    "RushFrame",
+    "HitFrame",
+    "ReturnFrame",
+    "FrameWidth",
+    "FrameHeight",
+    "Durations",
+];
+
+fn known_element_name(element: &XmlElement, names: &[&str]) -> Option<String> {
+    if names.contains(&element.name.as_str()) {
+        Some(element.name.clone())
+    } else {
+        None
+    }
+}
+
+impl AnimDataXML {
+    pub fn from_reader<R: BufRead>(source: R) -> Result<Self, SpriteBotStorageError> {
+        let root = parse_document(source)?;
+        let (known, mut extra) =
+            ExtraMetadata::split(root, |e| known_element_name(e, &ANIMDATA_ELEMENTS));
+
+        let mut shadow_size = None;
+        let mut anims = None;
+        for (key, element) in known {
+            if key == "ShadowSize" {
+                shadow_size = Some(element.parse_text("ShadowSize", None)?);
+                extra
+                    .children
+                    .push((key, ExtraMetadata::from_value_element(&element)));
+            } else {
+                let (anim_elements, anims_extra) = ExtraMetadata::split(element, |e| {
+                    if e.name == "Anim" {
+                        Some(anim_name(e))
+                    } else {
+                        None
+                    }
+                });
+                extra.children.push((key, anims_extra));
+                anims = Some(
+                    anim_elements
+                        .into_iter()
+                        .map(|(_, anim_element)| AnimXML::from_element(anim_element))
+                        .collect::<Result<_, _>>()?,
+                );
+            }
+        }
+
+        Ok(Self {
+            shadow_size: shadow_size.ok_or_else(|| SpriteBotStorageError::XmlMissingElement {
+                element: "ShadowSize",
+                animation: None,
+            })?,
+            anims: AnimsXML {
+                anim: anims.ok_or_else(|| SpriteBotStorageError::XmlMissingElement {
+                    element: "Anims",
+                    animation: None,
+                })?,
+            },
+            extra,
+        })
+    }
+
+    /// Format the AnimData.xml file like SpriteBot does, so re-saving an unmodified file produce an identical one.
+    ///
+    /// Known elements are written in the order they were read, or in SpriteBot’s order for new ones, and unknown elements and comments are put back next to the element they followed.
+    pub fn to_xml_string(&self) -> String {
+        let anims = self
+            .anims
+            .anim
+            .iter()
+            .map(|anim| (anim.name.clone(), anim.to_element()))
+            .collect();
+        let root = self.extra.merge(
+            "AnimData",
+            self.extra.in_source_order(vec![
+                self.extra
+                    .value_element("ShadowSize", self.shadow_size.to_string()),
+                (
+                    "Anims".to_string(),
+                    self.extra.child("Anims").merge("Anims", anims),
+                ),
+            ]),
+        );
+        let mut result = String::from("<?xml version=\"1.0\" ?>\n");
+        root.write_to(&mut result, "");
+        result
+    }
+}
+
+fn anim_name(anim_element: &XmlElement) -> String {
+    anim_element
+        .children
+        .iter()
+        .find_map(|child| match child {
+            XmlNode::Element(element) if element.name == "Name" => Some(element.text()),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+impl AnimXML {
+    fn from_element(element: XmlElement) -> Result<Self, SpriteBotStorageError> {
+        // Known before parsing the other elements, so their errors can tell which animation they are in
+        let animation_name = anim_name(&element);
+        let animation = Some(animation_name.as_str()).filter(|name| !name.is_empty());
+        let (known, mut extra) =
+            ExtraMetadata::split(element, |e| known_element_name(e, &ANIM_ELEMENTS));
+
+        let mut result = Self {
+            name: String::new(),
+            index: 0,
+            copy_of: None,
+            rush_frame: None,
+            hit_frame: None,
+            return_frame: None,
+            frame_width: None,
+            frame_height: None,
+            durations: None,
+            extra: ExtraMetadata::default(),
+        };
+        let mut name = None;
+        let mut index = None;
+        for (key, element) in known {
+            match key.as_str() {
+                "Name" => name = Some(element.text()),
+                "Index" => index = Some(element.parse_text("Index", animation)?),
+                "CopyOf" => result.copy_of = Some(element.text()),
+                "RushFrame" => {
+                    result.rush_frame = Some(element.parse_text("RushFrame", animation)?)
+                }
+                "HitFrame" => result.hit_frame = Some(element.parse_text("HitFrame", animation)?),
+                "ReturnFrame" => {
+                    result.return_frame = Some(element.parse_text("ReturnFrame", animation)?)
+                }
+                "FrameWidth" => {
+                    result.frame_width = Some(element.parse_text("FrameWidth", animation)?)
+                }
+                "FrameHeight" => {
+                    result.frame_height = Some(element.parse_text("FrameHeight", animation)?)
+                }
+                _ => {
+                    let (duration_elements, durations_extra) =
+                        ExtraMetadata::split(element, |e| known_element_name(e, &["Duration"]));
+                    extra.children.push((key, durations_extra));
+                    result.durations = Some(DurationsXML {
+                        duration: duration_elements
+                            .iter()
+                            .map(|(_, duration)| duration.parse_text("Duration", animation))
+                            .collect::<Result<_, _>>()?,
+                    });
+                    continue;
+                }
+            }
+            extra
+                .children
+                .push((key, ExtraMetadata::from_value_element(&element)));
+        }
+
+        result.name = name.ok_or(SpriteBotStorageError::XmlMissingElement {
+            element: "Name",
+            animation: None,
+        })?;
+        result.index = index.ok_or_else(|| SpriteBotStorageError::XmlMissingElement {
+            element: "Index",
+            animation: Some(result.name.clone()),
+        })?;
+        result.extra = extra;
+        Ok(result)
+    }
+
+    fn to_element(&self) -> XmlElement {
+        let extra = &self.extra;
+        let mut known = vec![
+            extra.value_element("Name", self.name.clone()),
+            extra.value_element("Index", self.index.to_string()),
+        ];
+        let optional_values = [
+            ("CopyOf", self.copy_of.clone()),
+            ("RushFrame", self.rush_frame.map(|x| x.to_string())),
+            ("HitFrame", self.hit_frame.map(|x| x.to_string())),
+            ("ReturnFrame", self.return_frame.map(|x| x.to_string())),
+            ("FrameWidth", self.frame_width.map(|x| x.to_string())),
+            ("FrameHeight", self.frame_height.map(|x| x.to_string())),
+        ];
+        for (name, value) in optional_values {
+            if let Some(value) = value {
+                known.push(extra.value_element(name, value));
+            }
+        }
+        if let Some(durations) = &self.durations {
+            let durations_extra = extra.child("Durations");
+            let duration_elements = durations
+                .duration
+                .iter()
+                .map(|duration| durations_extra.value_element("Duration", duration.to_string()))
+                .collect();
+            known.push((
+                "Durations".to_string(),
+                durations_extra.merge("Durations", duration_elements),
+            ));
+        }
+        extra.merge("Anim", extra.in_source_order(known))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Formatted like SpriteBot, but with unknown content and known elements out of SpriteBot’s order
+    const UNUSUAL_ANIMDATA: &str = r#"<?xml version="1.0" ?>
+<AnimData version="2">
+	<!--Edited by hand-->
+	<ShadowSize>1</ShadowSize>
+	<Anims>
+		<Anim>
+			<Name>Attack</Name>
+			<Index>5</Index>
+			<FrameWidth>24</FrameWidth>
+			<FrameHeight>32</FrameHeight>
+			<RushFrame>2</RushFrame>
+			<HitFrame>3</HitFrame>
+			<ReturnFrame>4</ReturnFrame>
+			<Durations>
+				<Duration>2</Duration>
+				<Duration>6</Duration>
+			</Durations>
+			<Sound volume="0.5">hit.wav</Sound>
+		</Anim>
+		<Anim>
+			<Index>7</Index>
+			<Name>Idle</Name>
+			<CopyOf>Attack</CopyOf>
+		</Anim>
+	</Anims>
+	<Author>Someone</Author>
+</AnimData>
+"#;
+
+    #[test]
+    fn unknown_content_and_element_order_are_kept() {
+        let animdata = AnimDataXML::from_reader(UNUSUAL_ANIMDATA.as_bytes()).unwrap();
+        assert_eq!(animdata.anims.anim[0].rush_frame, Some(2));
+        assert_eq!(animdata.anims.anim[1].copy_of.as_deref(), Some("Attack"));
+        assert!(!animdata.extra.is_empty());
+        assert_eq!(animdata.to_xml_string(), UNUSUAL_ANIMDATA);
+    }
+
+    #[test]
+    fn new_elements_follow_spritebot_order() {
+        let mut animdata = AnimDataXML::from_reader(UNUSUAL_ANIMDATA.as_bytes()).unwrap();
+        animdata.anims.anim[1].copy_of = None;
+        animdata.anims.anim[1].frame_width = Some(8);
+        animdata.anims.anim[1].rush_frame = Some(1);
+        animdata.anims.anim[0].rush_frame = None;
+
+        let written = animdata.to_xml_string();
+        assert!(written.contains(
+            "<Index>5</Index>\n\t\t\t<FrameWidth>24</FrameWidth>\n\t\t\t<FrameHeight>32</FrameHeight>\n\t\t\t<HitFrame>3</HitFrame>"
+        ));
+        assert!(written.contains(
+            "<Index>7</Index>\n\t\t\t<Name>Idle</Name>\n\t\t\t<RushFrame>1</RushFrame>\n\t\t\t<FrameWidth>8</FrameWidth>\n\t\t</Anim>"
+        ));
+    }
+
+    #[test]
+    fn errors_tell_the_animation() {
+        let missing_index = UNUSUAL_ANIMDATA.replace("<Index>7</Index>", "");
+        assert!(matches!(
+            AnimDataXML::from_reader(missing_index.as_bytes()),
+            Err(SpriteBotStorageError::XmlMissingElement { element: "Index", animation: Some(animation) })
+                if animation == "Idle"
+        ));
+
+        let invalid_value = UNUSUAL_ANIMDATA.replace("<Index>7</Index>", "<Index>seven</Index>");
+        assert!(matches!(
+            AnimDataXML::from_reader(invalid_value.as_bytes()),
+            Err(SpriteBotStorageError::XmlInvalidValue { element: "Index", animation: Some(animation), value })
+                if animation == "Idle" && value == "seven"
+        ));
+
+        let invalid_shadow_size = UNUSUAL_ANIMDATA.replace(">1</ShadowSize>", ">-1</ShadowSize>");
+        assert!(matches!(
+            AnimDataXML::from_reader(invalid_shadow_size.as_bytes()),
+            Err(SpriteBotStorageError::XmlInvalidValue {
+                element: "ShadowSize",
+                animation: None,
+                ..
+            })
+        ));
+    }
+}
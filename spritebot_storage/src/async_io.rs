@@ -0,0 +1,321 @@
+use std::io::Write;
+
+use blocking::unblock;
+use futures::{AsyncReadExt, AsyncWriteExt, StreamExt};
+use vfs::{async_vfs::AsyncVfsPath, FileSystem, MemoryFS, VfsError, VfsResult};
+
+use crate::{
+    folder::{RootFolder, SpriteFolder},
+    read_anim_fields, sheet_file_name,
+    write_plan::{FileOperation, WritePlan},
+    AnimDataXML, EncodedAnimation, ImageKind, SkippedAnimation, Sprite, SpriteBotStorageError,
+    WriteOptions, ANIMDATA_FILE_NAME,
+};
+
+/// The full path of a file in the folder, like for a [`vfs::VfsPath`]
+fn file_path(folder: &AsyncVfsPath, file_name: &str) -> String {
+    format!("{}/{}", folder.as_str(), file_name)
+}
+
+fn vfs_error(folder: &AsyncVfsPath, file_name: &str, source: VfsError) -> SpriteBotStorageError {
+    SpriteBotStorageError::VfsError {
+        source,
+        path: file_path(folder, file_name),
+    }
+}
+
+async fn read_vfs_file(folder: &AsyncVfsPath, file_name: &str) -> VfsResult<Vec<u8>> {
+    let mut file = folder.join(file_name)?.open_file().await?;
+    let mut content = Vec::new();
+    file.read_to_end(&mut content).await?;
+    Ok(content)
+}
+
+async fn read_file(
+    folder: &AsyncVfsPath,
+    file_name: &str,
+) -> Result<Vec<u8>, SpriteBotStorageError> {
+    read_vfs_file(folder, file_name)
+        .await
+        .map_err(|source| vfs_error(folder, file_name, source))
+}
+
+/// Create (or replace) the file with the given content
+async fn write_file(
+    folder: &AsyncVfsPath,
+    file_name: &str,
+    content: &[u8],
+) -> Result<(), SpriteBotStorageError> {
+    let mut file = async { folder.join(file_name)?.create_file().await }
+        .await
+        .map_err(|source| vfs_error(folder, file_name, source))?;
+    let write_error = |source| SpriteBotStorageError::WriteFileError {
+        source,
+        path: file_path(folder, file_name),
+    };
+    file.write_all(content).await.map_err(write_error)?;
+    // Async writers may only write the end of the file once closed
+    file.close().await.map_err(write_error)
+}
+
+async fn remove_vfs_file(folder: &AsyncVfsPath, file_name: &str) -> VfsResult<()> {
+    folder.join(file_name)?.remove_file().await
+}
+
+async fn remove_file(folder: &AsyncVfsPath, file_name: &str) -> Result<(), SpriteBotStorageError> {
+    remove_vfs_file(folder, file_name)
+        .await
+        .map_err(|source| vfs_error(folder, file_name, source))
+}
+
+/// Move the file `from` to `to`, which must not exist, like [`SpriteFolder::move_vfs_file`]. [`AsyncVfsPath::move_file`] already copy the file if the file system can’t move it.
+async fn move_file(
+    folder: &AsyncVfsPath,
+    from: &str,
+    to: &str,
+) -> Result<(), SpriteBotStorageError> {
+    async { folder.join(from)?.move_file(&folder.join(to)?).await }
+        .await
+        .map_err(|source| vfs_error(folder, from, source))
+}
+
+/// Run an operation of a [`WritePlan`], like [`SpriteFolder::apply`]
+async fn apply(
+    folder: &AsyncVfsPath,
+    operation: FileOperation<'_>,
+) -> Result<(), SpriteBotStorageError> {
+    match operation {
+        FileOperation::Write { file_name, content } => {
+            write_file(folder, &file_name, content).await
+        }
+        FileOperation::Move { from, to } => move_file(folder, &from, &to).await,
+        FileOperation::Remove { file_name } => remove_file(folder, &file_name).await,
+    }
+}
+
+async fn list_vfs_files(folder: &AsyncVfsPath) -> VfsResult<Vec<String>> {
+    Ok(folder
+        .read_dir()
+        .await?
+        .map(|path| path.filename())
+        .collect()
+        .await)
+}
+
+/// Read the sheets of the animation, in the order of [`ImageKind::ALL`]
+async fn read_sheet_files(
+    folder: &AsyncVfsPath,
+    name: &str,
+) -> Result<Vec<Vec<u8>>, SpriteBotStorageError> {
+    let mut sheets = Vec::new();
+    for kind in ImageKind::ALL {
+        sheets.push(read_file(folder, &sheet_file_name(name, kind)).await?);
+    }
+    Ok(sheets)
+}
+
+/// Copy in memory the sheets of the folder of the animations that weren’t modified since they were loaded, so they can be used by `Sprite::generate_files` for incremental writes. Files that can’t be read are skipped.
+async fn read_previous_files(folder: &AsyncVfsPath, sprite: &Sprite) -> MemoryFS {
+    let previous = MemoryFS::new();
+    for animation in &sprite.animations {
+        if animation.unchanged_origin().is_none() {
+            continue;
+        }
+        for kind in ImageKind::ALL {
+            let file_name = sheet_file_name(&animation.name, kind);
+            if let Ok(content) = read_vfs_file(folder, &file_name).await {
+                // Writing to a MemoryFS can’t fail
+                let _ = previous
+                    .create_file(&format!("/{}", file_name))
+                    .and_then(|mut file| Ok(file.write_all(&content)?));
+            }
+        }
+    }
+    previous
+}
+
+impl Sprite {
+    /// Read the sprite in the given folder of an async file system, like [`Sprite::new_from_path`].
+    ///
+    /// The files are read asynchronously, then the sheets are decoded on the thread pool of the [`blocking`] crate, so this doesn’t block the async runtime, whichever it is.
+    pub async fn new_from_async_path(path: &AsyncVfsPath) -> Result<Self, SpriteBotStorageError> {
+        Self::load_async(path, false)
+            .await
+            .map(|(sprite, _)| sprite)
+    }
+
+    /// Read the sprite in the given folder of an async file system, like [`Sprite::new_lenient`]
+    pub async fn new_lenient_from_async_path(
+        path: &AsyncVfsPath,
+    ) -> Result<(Self, Vec<SkippedAnimation>), SpriteBotStorageError> {
+        Self::load_async(path, true).await
+    }
+
+    async fn load_async(
+        folder: &AsyncVfsPath,
+        lenient: bool,
+    ) -> Result<(Self, Vec<SkippedAnimation>), SpriteBotStorageError> {
+        let animdata_content = read_file(folder, ANIMDATA_FILE_NAME).await?;
+        let animdata_xml = AnimDataXML::from_reader(animdata_content.as_slice())?;
+
+        // Everything EncodedAnimation::read get, for animations that aren’t a CopyOf
+        let mut read_animations = Vec::new();
+        for anim_source in &animdata_xml.anims.anim {
+            if anim_source.copy_of.is_some() {
+                read_animations.push(None);
+                continue;
+            }
+            let read_animation = match read_anim_fields(anim_source) {
+                Ok(fields) => read_sheet_files(folder, &anim_source.name)
+                    .await
+                    .map(|sheets| (fields, sheets)),
+                Err(err) => Err(err),
+            };
+            read_animations.push(Some(read_animation));
+        }
+
+        unblock(move || {
+            let encoded = animdata_xml
+                .anims
+                .anim
+                .iter()
+                .zip(read_animations)
+                .map(|(anim_source, read_animation)| {
+                    read_animation.map(|read_animation| {
+                        read_animation.map(|((frame_size, durations), sheets)| EncodedAnimation {
+                            source: anim_source,
+                            frame_size,
+                            durations,
+                            sheets,
+                        })
+                    })
+                })
+                .collect();
+            Self::from_encoded(&animdata_xml, encoded, lenient)
+        })
+        .await
+    }
+
+    /// Write the sprite in the given folder of an async file system, creating it if needed, like [`Sprite::write_to_path`].
+    ///
+    /// The sheets are generated and encoded on the thread pool of the [`blocking`] crate, from a copy of the sprite. Copying frames that still share the pixels of the sheet they were loaded from is cheap, see [`crate::FrameImage`], but frames that were created or modified own their pixels, and are copied too. For a sprite built in memory, this copy every image once, and take as much memory again as the frames. To avoid it, call [`Sprite::write_to_path`] on a thread that may block instead.
+    ///
+    /// The files are then replaced like [`Sprite::write_to_path`] do, so a failure doesn’t leave a mix of old and new files.
+    pub async fn write_to_async_path(
+        &self,
+        path: &AsyncVfsPath,
+        options: &WriteOptions,
+    ) -> Result<(), SpriteBotStorageError> {
+        path.create_dir_all()
+            .await
+            .map_err(|source| SpriteBotStorageError::VfsError {
+                source,
+                path: path.as_str().to_string(),
+            })?;
+        let previous = if options.incremental {
+            Some(read_previous_files(path, self).await)
+        } else {
+            None
+        };
+        let sprite = self.clone();
+        let options = options.clone();
+        let files = unblock(move || {
+            let previous = previous.as_ref().map(RootFolder);
+            sprite.generate_files(
+                &options,
+                previous.as_ref().map(|folder| folder as &dyn SpriteFolder),
+            )
+        })
+        .await?;
+
+        let existing_files = list_vfs_files(path)
+            .await
+            .map_err(|source| vfs_error(path, "", source))?;
+        WritePlan::new(&files, &existing_files)
+            .run_async(|operation| apply(path, operation))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_std::task::block_on;
+    use vfs::async_vfs::AsyncMemoryFS;
+
+    use super::*;
+    use crate::tests::{test_animation, test_sprite};
+
+    async fn file_names(folder: &AsyncVfsPath) -> Vec<String> {
+        let mut names = list_vfs_files(folder).await.unwrap();
+        names.sort();
+        names
+    }
+
+    /// A folder of an async file system, with a sprite of two animations
+    async fn sprite_folder() -> (AsyncVfsPath, Sprite) {
+        let idle = test_animation("Idle", 7, 8, &[10, 20], 1);
+        let walk = test_animation("Walk", 0, 8, &[4, 4], 100);
+        let sprite = test_sprite(vec![idle, walk]);
+        let folder = AsyncVfsPath::new(AsyncMemoryFS::new())
+            .join("sprite/0025")
+            .unwrap();
+        sprite
+            .write_to_async_path(&folder, &WriteOptions::default())
+            .await
+            .unwrap();
+        (folder, sprite)
+    }
+
+    #[test]
+    fn async_round_trip_and_incremental_write() {
+        block_on(async {
+            let (folder, written) = sprite_folder().await;
+            let mut sprite = Sprite::new_from_async_path(&folder).await.unwrap();
+            assert_eq!(sprite.animations.len(), 2);
+            for (read, animation) in sprite.animations.iter().zip(&written.animations) {
+                assert!(read.has_same_content(animation));
+            }
+
+            sprite.animations.remove(1);
+            let idle_sheet = read_vfs_file(&folder, "Idle-Anim.png").await.unwrap();
+            let options = WriteOptions {
+                incremental: true,
+                ..WriteOptions::default()
+            };
+            sprite.write_to_async_path(&folder, &options).await.unwrap();
+            assert_eq!(
+                file_names(&folder).await,
+                vec![
+                    "AnimData.xml",
+                    "Idle-Anim.png",
+                    "Idle-Offsets.png",
+                    "Idle-Shadow.png"
+                ]
+            );
+            assert_eq!(
+                read_vfs_file(&folder, "Idle-Anim.png").await.unwrap(),
+                idle_sheet
+            );
+            let read = Sprite::new_from_async_path(&folder).await.unwrap();
+            assert_eq!(read.animations.len(), 1);
+            assert!(read.animations[0].has_same_content(&written.animations[0]));
+        });
+    }
+
+    #[test]
+    fn only_sheets_of_unmodified_animations_are_read_for_incremental_writes() {
+        block_on(async {
+            let (folder, _) = sprite_folder().await;
+            let mut sprite = Sprite::new_from_async_path(&folder).await.unwrap();
+            sprite.animations[1].images[0][0].offsets.center = (5, 5);
+
+            let previous = read_previous_files(&folder, &sprite).await;
+            let mut names = previous.read_dir("").unwrap().collect::<Vec<_>>();
+            names.sort();
+            assert_eq!(
+                names,
+                vec!["Idle-Anim.png", "Idle-Offsets.png", "Idle-Shadow.png"]
+            );
+        });
+    }
+}
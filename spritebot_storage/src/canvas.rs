@@ -0,0 +1,208 @@
+use image::GenericImageView;
+
+use crate::{Animation, Frame, Sprite, SpriteBotStorageError};
+
+impl Frame {
+    /// The frame with `left`, `top`, `right` and `bottom` transparent pixels added on each side. The offsets are moved with the image.
+    ///
+    /// Return `None` if the frame would be more than 2^32 - 1 pixels in width or height, or an offset more than 65535.
+    pub fn padded(&self, left: u32, top: u32, right: u32, bottom: u32) -> Option<Frame> {
+        let (width, height) = self.image.dimensions();
+        self.reframed(
+            -(left as i64),
+            -(top as i64),
+            width.checked_add(left)?.checked_add(right)?,
+            height.checked_add(top)?.checked_add(bottom)?,
+        )
+    }
+
+    /// The rectangle of the frame starting at (`x`, `y`), with the offsets moved to match.
+    ///
+    /// Return `None` if a pixel that isn’t fully transparent or an offset is outside of the rectangle.
+    pub fn cropped(&self, x: u32, y: u32, width: u32, height: u32) -> Option<Frame> {
+        self.reframed(x as i64, y as i64, width, height)
+    }
+
+    /// The frame with its pixels and offsets moved by `delta`, keeping its size.
+    ///
+    /// Return `None` if a pixel that isn’t fully transparent or an offset would be moved outside of the frame.
+    pub fn translated(&self, delta: (i32, i32)) -> Option<Frame> {
+        let (width, height) = self.image.dimensions();
+        self.reframed(-(delta.0 as i64), -(delta.1 as i64), width, height)
+    }
+
+    /// The frame with a new canvas, which is the rectangle at (`x`, `y`) in the current frame. `x` and `y` are negative to add pixels on the top and left.
+    fn reframed(&self, x: i64, y: i64, width: u32, height: u32) -> Option<Frame> {
+        let inside = |position: (i64, i64)| {
+            position.0 >= x
+                && position.1 >= y
+                && position.0 < x + width as i64
+                && position.1 < y + height as i64
+        };
+        if let Some((min_x, min_y, max_x, max_y)) = self.image.opaque_bounds() {
+            if !inside((min_x as i64, min_y as i64)) || !inside((max_x as i64, max_y as i64)) {
+                return None;
+            }
+        }
+        if !self
+            .offsets
+            .positions()
+            .iter()
+            .all(|position| inside((position.0 as i64, position.1 as i64)))
+        {
+            return None;
+        }
+        let offsets = self
+            .offsets
+            .translated(((-x).try_into().ok()?, (-y).try_into().ok()?))?;
+        Some(Frame {
+            image: self.image.region(x, y, width, height),
+            offsets,
+        })
+    }
+}
+
+/// Why a canvas operation can fail, to report it
+#[derive(Clone, Copy)]
+enum CanvasFailure {
+    /// Some content would be outside of the new canvas
+    Clipped,
+    /// The new canvas would be too large
+    TooLarge,
+}
+
+impl CanvasFailure {
+    fn error(self, animation: &str, direction: usize, frame: usize) -> SpriteBotStorageError {
+        let animation = animation.to_string();
+        match self {
+            Self::Clipped => SpriteBotStorageError::ContentClipped {
+                animation,
+                direction,
+                frame,
+            },
+            Self::TooLarge => SpriteBotStorageError::CanvasTooLarge {
+                animation,
+                direction,
+                frame,
+            },
+        }
+    }
+}
+
+impl Animation {
+    /// Add transparent pixels on each side of every frame, like [`Frame::padded`]
+    ///
+    /// The animation is unchanged if it fails.
+    pub fn pad(
+        &mut self,
+        left: u32,
+        top: u32,
+        right: u32,
+        bottom: u32,
+    ) -> Result<(), SpriteBotStorageError> {
+        self.images = self.map_frames(CanvasFailure::TooLarge, |frame| {
+            frame.padded(left, top, right, bottom)
+        })?;
+        Ok(())
+    }
+
+    /// Crop every frame to the same rectangle, like [`Frame::cropped`]
+    ///
+    /// The animation is unchanged if it fails.
+    pub fn crop(
+        &mut self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), SpriteBotStorageError> {
+        self.images = self.map_frames(CanvasFailure::Clipped, |frame| {
+            frame.cropped(x, y, width, height)
+        })?;
+        Ok(())
+    }
+
+    /// Move the pixels and offsets of every frame, like [`Frame::translated`]
+    ///
+    /// The animation is unchanged if it fails.
+    pub fn translate(&mut self, delta: (i32, i32)) -> Result<(), SpriteBotStorageError> {
+        self.images = self.map_frames(CanvasFailure::Clipped, |frame| frame.translated(delta))?;
+        Ok(())
+    }
+
+    /// Apply the canvas operation to every frame, failing with `failure` at the first frame it returns `None` for
+    fn map_frames(
+        &self,
+        failure: CanvasFailure,
+        operation: impl Fn(&Frame) -> Option<Frame>,
+    ) -> Result<Vec<Vec<Frame>>, SpriteBotStorageError> {
+        self.images
+            .iter()
+            .enumerate()
+            .map(|(direction_nb, line)| {
+                line.iter()
+                    .enumerate()
+                    .map(|(frame_nb, frame)| {
+                        operation(frame)
+                            .ok_or_else(|| failure.error(&self.name, direction_nb, frame_nb))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl Sprite {
+    /// Add transparent pixels on each side of every frame of every animation, like [`Frame::padded`]
+    ///
+    /// The sprite is unchanged if it fails.
+    pub fn pad(
+        &mut self,
+        left: u32,
+        top: u32,
+        right: u32,
+        bottom: u32,
+    ) -> Result<(), SpriteBotStorageError> {
+        self.map_animations(CanvasFailure::TooLarge, |frame| {
+            frame.padded(left, top, right, bottom)
+        })
+    }
+
+    /// Crop every frame of every animation to the same rectangle, like [`Frame::cropped`]
+    ///
+    /// The sprite is unchanged if it fails.
+    pub fn crop(
+        &mut self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), SpriteBotStorageError> {
+        self.map_animations(CanvasFailure::Clipped, |frame| {
+            frame.cropped(x, y, width, height)
+        })
+    }
+
+    /// Move the pixels and offsets of every frame of every animation, like [`Frame::translated`]
+    ///
+    /// The sprite is unchanged if it fails.
+    pub fn translate(&mut self, delta: (i32, i32)) -> Result<(), SpriteBotStorageError> {
+        self.map_animations(CanvasFailure::Clipped, |frame| frame.translated(delta))
+    }
+
+    fn map_animations(
+        &mut self,
+        failure: CanvasFailure,
+        operation: impl Fn(&Frame) -> Option<Frame>,
+    ) -> Result<(), SpriteBotStorageError> {
+        let images = self
+            .animations
+            .iter()
+            .map(|animation| animation.map_frames(failure, &operation))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (animation, images) in self.animations.iter_mut().zip(images) {
+            animation.images = images;
+        }
+        Ok(())
+    }
+}
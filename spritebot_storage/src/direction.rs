@@ -0,0 +1,151 @@
+use std::f32::consts::FRAC_PI_4;
+
+/// A direction a sprite can face. The order of the variants is the order of the rows in the sheets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Down,
+    DownRight,
+    Right,
+    UpRight,
+    Up,
+    UpLeft,
+    Left,
+    DownLeft,
+}
+
+/// The directions by angle, each being 45° counter-clockwise from the previous one
+const DIRECTIONS_BY_ANGLE: [Direction; 8] = [
+    Direction::Right,
+    Direction::UpRight,
+    Direction::Up,
+    Direction::UpLeft,
+    Direction::Left,
+    Direction::DownLeft,
+    Direction::Down,
+    Direction::DownRight,
+];
+
+impl Direction {
+    /// All the directions, in the order of the rows in the sheets
+    pub const ALL: [Direction; 8] = [
+        Direction::Down,
+        Direction::DownRight,
+        Direction::Right,
+        Direction::UpRight,
+        Direction::Up,
+        Direction::UpLeft,
+        Direction::Left,
+        Direction::DownLeft,
+    ];
+
+    /// The row of this direction in the sheets
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn from_index(index: usize) -> Option<Self> {
+        Self::ALL.get(index).copied()
+    }
+
+    /// Get the direction nearest to the given angle, in radian.
+    ///
+    /// The angle is counter-clockwise as seen on screen, with 0 being [`Direction::Right`] and π/2 being [`Direction::Up`].
+    ///
+    /// Return `None` if the angle is NaN or infinite, as it doesn’t point anywhere.
+    pub fn from_angle(angle: f32) -> Option<Self> {
+        if !angle.is_finite() {
+            return None;
+        }
+        let sector = (angle / FRAC_PI_4).round() as i64;
+        Some(DIRECTIONS_BY_ANGLE[sector.rem_euclid(8) as usize])
+    }
+
+    /// Get the direction nearest to the given vector, in screen coordinates (the y axis is going down).
+    ///
+    /// Return `None` for the null vector, which has no direction, and if either coordinate is NaN.
+    pub fn from_vector(x: f32, y: f32) -> Option<Self> {
+        if x == 0.0 && y == 0.0 {
+            return None;
+        }
+        Self::from_angle((-y).atan2(x))
+    }
+
+    /// The angle of this direction, in radian, with the same convention as [`Direction::from_angle`]
+    pub fn angle(self) -> f32 {
+        let position = DIRECTIONS_BY_ANGLE
+            .iter()
+            .position(|direction| *direction == self)
+            .unwrap(); // all directions are in DIRECTIONS_BY_ANGLE
+        position as f32 * FRAC_PI_4
+    }
+}
+
+/// How many directions an animation has
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DirectionLayout {
+    /// A single row, used for all directions
+    Single,
+    /// One row for each of the 8 [`Direction`]s
+    Eight,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+    use super::Direction;
+
+    #[test]
+    fn from_angle_picks_the_nearest_direction() {
+        assert_eq!(Direction::from_angle(0.0), Some(Direction::Right));
+        assert_eq!(Direction::from_angle(FRAC_PI_2), Some(Direction::Up));
+        assert_eq!(Direction::from_angle(PI), Some(Direction::Left));
+        assert_eq!(Direction::from_angle(-FRAC_PI_2), Some(Direction::Down));
+        assert_eq!(
+            Direction::from_angle(-FRAC_PI_4),
+            Some(Direction::DownRight)
+        );
+        // Just on either side of the limit between Right and UpRight
+        assert_eq!(
+            Direction::from_angle(FRAC_PI_4 / 2.0 - 0.01),
+            Some(Direction::Right)
+        );
+        assert_eq!(
+            Direction::from_angle(FRAC_PI_4 / 2.0 + 0.01),
+            Some(Direction::UpRight)
+        );
+        // Angles wrap around
+        assert_eq!(
+            Direction::from_angle(2.0 * PI + FRAC_PI_2),
+            Some(Direction::Up)
+        );
+        assert_eq!(Direction::from_angle(-3.0 * FRAC_PI_2), Some(Direction::Up));
+
+        assert_eq!(Direction::from_angle(f32::NAN), None);
+        assert_eq!(Direction::from_angle(f32::INFINITY), None);
+        assert_eq!(Direction::from_angle(f32::NEG_INFINITY), None);
+    }
+
+    #[test]
+    fn angle_round_trips() {
+        for direction in Direction::ALL {
+            assert_eq!(Direction::from_angle(direction.angle()), Some(direction));
+            assert_eq!(Direction::from_index(direction.index()), Some(direction));
+        }
+        assert_eq!(Direction::from_index(8), None);
+    }
+
+    #[test]
+    fn from_vector_uses_screen_coordinates() {
+        assert_eq!(Direction::from_vector(1.0, 0.0), Some(Direction::Right));
+        assert_eq!(Direction::from_vector(0.0, 1.0), Some(Direction::Down));
+        assert_eq!(Direction::from_vector(0.0, -1.0), Some(Direction::Up));
+        assert_eq!(Direction::from_vector(-1.0, 0.0), Some(Direction::Left));
+        assert_eq!(Direction::from_vector(1.0, 1.0), Some(Direction::DownRight));
+        assert_eq!(Direction::from_vector(-2.0, -2.0), Some(Direction::UpLeft));
+        assert_eq!(Direction::from_vector(10.0, 1.0), Some(Direction::Right));
+
+        assert_eq!(Direction::from_vector(0.0, 0.0), None);
+        assert_eq!(Direction::from_vector(f32::NAN, 1.0), None);
+    }
+}
@@ -1,44 +1,211 @@
-use std::{io, num::TryFromIntError};
+use std::{fmt::Display, io, num::TryFromIntError};
 
 use image::ImageError;
 use thiserror::Error;
 use vfs::VfsError;
 
+use crate::{ImageKind, OffsetKind};
+
+/// An axis of an image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Axis {
+    Width,
+    Height,
+}
+
+impl Display for Axis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Width => "width",
+            Self::Height => "height",
+        })
+    }
+}
+
+/// The animation an element of AnimData.xml is in, to complete an error message
+fn of_animation(animation: &Option<String>) -> String {
+    match animation {
+        Some(animation) => format!(" of the animation {}", animation),
+        None => String::new(),
+    }
+}
+
+/// An error while reading or writing a sprite.
+///
+/// `direction` fields are the index of the direction (the row in the sheet, see [`crate::Direction::from_index`]), and `frame` fields are the index of the frame in that direction. Pixel positions are relative to the whole sheet.
 #[derive(Error, Debug)]
 #[non_exhaustive]
 pub enum SpriteBotStorageError {
-    #[error("Error with the underlying virtual file system with the file {0}")]
-    VfsError(#[source] VfsError, String),
+    #[error("Error with the underlying virtual file system with the file {path}")]
+    VfsError {
+        #[source]
+        source: VfsError,
+        path: String,
+    },
+    #[error(
+        "The animation {animation} is a copy of {copy_of}, but no animation with that name exist"
+    )]
+    CopyOfNotFound { animation: String, copy_of: String },
+    #[error("The animation {animation} is a copy of {copy_of}, which couldn’t be loaded")]
+    CopyOfSkipped { animation: String, copy_of: String },
+    #[error("The animation {animation} is part of a loop of CopyOf")]
+    CopyOfCycle { animation: String },
+    #[error("The animation {animation} has no {field}, and isn’t a CopyOf either")]
+    MissingAnimField {
+        animation: String,
+        field: &'static str,
+    },
     #[error("Error while reading the AnimData.xml file")]
-    XmlReadError(#[from] quick_xml::DeError),
-    #[error("Error while writing the AnimData.xml file")]
-    XmlWriteError(#[from] quick_xml::SeError),
-    #[error("Error reading the image {0} for the animation {1}")]
-    ErrorImageRead(String, String, #[source] image::ImageError),
-    #[error("The {0} for the image {1}-{2} is zero")]
-    SpriteSizeZero(String, String, String),
-    #[error("The {0} of the image {1}-{2} isn’t a multiple of it’s set {0}")]
-    SpriteSizeNotMultiple(String, String, String),
-    #[error("The images for the animation {0} doesn’t have an identical size")]
-    SpriteSizeNotIdentical(String),
-    #[error("The image for the animation {0} has size for {1} frame per column, but the frame duration data in the XML account for {2} frames.")]
-    InconsistantDuration(String, usize, usize),
-    #[error("For the animation {0}, column {1} row {2}: In the image for {3}, the pixel with the color {4} can’t be found")]
-    ColorNotFoundInPixelData(String, usize, usize, String, String),
-    #[error("For the animation {0}, column {1} row {2}: In the image for {3}, there are multiple {4} pixels")]
-    ColorDuplicateInPixelDate(String, usize, usize, String, String),
-    #[error("There is a too large duration (max is about 255 frames)")]
-    TooLargeDuration(#[source] TryFromIntError),
-    #[error("The dimension of a sheet is too large (more than 2^32). You probably have an insanly large amount of image.")]
-    TooLargeGeneratedSheet(#[source] TryFromIntError),
+    XmlReadError {
+        #[from]
+        source: quick_xml::Error,
+    },
+    #[error(
+        "The element {element}{} is missing in the AnimData.xml file",
+        of_animation(animation)
+    )]
+    XmlMissingElement {
+        element: &'static str,
+        /// The animation the element should be in, if it is part of one
+        animation: Option<String>,
+    },
+    #[error(
+        "The element {element}{} in the AnimData.xml file has the invalid value {value:?}",
+        of_animation(animation)
+    )]
+    XmlInvalidValue {
+        element: &'static str,
+        /// The animation the element is in, if it is part of one that has a name
+        animation: Option<String>,
+        value: String,
+    },
+    #[error("Error reading the {image_kind} image for the animation {animation}")]
+    ErrorImageRead {
+        animation: String,
+        image_kind: ImageKind,
+        #[source]
+        source: ImageError,
+    },
+    #[error("The frame {axis} for the image {animation}-{image_kind} is zero")]
+    SpriteSizeZero {
+        animation: String,
+        image_kind: ImageKind,
+        axis: Axis,
+    },
+    #[error("The {axis} of the image {animation}-{image_kind} ({size}) isn’t a multiple of its frame {axis} ({frame_size})")]
+    SpriteSizeNotMultiple {
+        animation: String,
+        image_kind: ImageKind,
+        axis: Axis,
+        size: u32,
+        frame_size: u32,
+    },
+    #[error("The image {animation}-{image_kind} has a size of {actual:?}, but the Anim image has a size of {expected:?}")]
+    SpriteSizeNotIdentical {
+        animation: String,
+        image_kind: ImageKind,
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+    #[error("The image for the animation {animation} has size for {frame_count} frame per direction, but the frame duration data in the XML account for {duration_count} frames.")]
+    InconsistantDuration {
+        animation: String,
+        frame_count: usize,
+        duration_count: usize,
+    },
+    #[error("The direction {direction} of the animation {animation} has {frame_count} frames, but there are {duration_count} durations")]
+    InconsistentFrameCount {
+        animation: String,
+        direction: usize,
+        frame_count: usize,
+        duration_count: usize,
+    },
+    #[error("For the animation {animation}, direction {direction} frame {frame}: In the {image_kind} image, the {} pixel of the {offset} can’t be found", offset.color_name())]
+    ColorNotFoundInPixelData {
+        animation: String,
+        direction: usize,
+        frame: usize,
+        image_kind: ImageKind,
+        offset: OffsetKind,
+    },
+    #[error("For the animation {animation}, direction {direction} frame {frame}: In the {image_kind} image, there are multiple {} pixels for the {offset}, at {pixels:?}", offset.color_name())]
+    ColorDuplicateInPixelDate {
+        animation: String,
+        direction: usize,
+        frame: usize,
+        image_kind: ImageKind,
+        offset: OffsetKind,
+        pixels: Vec<(u32, u32)>,
+    },
+    #[error("The duration {duration} of the frame {frame} of the animation {animation} is too large (max is 2^32 - 1 ticks)")]
+    TooLargeDuration {
+        animation: String,
+        frame: usize,
+        duration: usize,
+        #[source]
+        source: TryFromIntError,
+    },
+    #[error("The durations of the animation {animation} can’t be multiplied by {factor}, which isn’t a finite number greater than zero")]
+    InvalidRetimeFactor { animation: String, factor: f32 },
+    #[error("The dimension of the sheet for the animation {animation} is too large (more than 2^32). You probably have an insanly large amount of image.")]
+    TooLargeGeneratedSheet {
+        animation: String,
+        #[source]
+        source: TryFromIntError,
+    },
     #[error(
-        "The position of the head offset in file is invalid, as it would overwrite an hand offset"
+        "For the animation {animation}, direction {direction} frame {frame}: the head offset at {head:?} is invalid, as it would overwrite an hand offset"
     )]
-    InvalidHeadPosition,
-    #[error("Error writing the image at {1}")]
-    WriteImageError(#[source] ImageError, String),
-    #[error("Error writing to {1}")]
-    WriteFileError(#[source] io::Error, String),
-    #[error("An offset is placed somewhere too far from the original (should only happen on sheet greater than 2^16 in width or height)")]
-    OffsetTooLarge,
+    InvalidHeadPosition {
+        animation: String,
+        direction: usize,
+        frame: usize,
+        head: (u16, u16),
+    },
+    #[error("Error writing the image at {path}")]
+    WriteImageError {
+        #[source]
+        source: ImageError,
+        path: String,
+    },
+    #[error("Error writing to {path}")]
+    WriteFileError {
+        #[source]
+        source: io::Error,
+        path: String,
+    },
+    #[cfg(feature = "zip")]
+    #[error("Error with the zip archive")]
+    ZipError {
+        #[from]
+        source: zip::result::ZipError,
+    },
+    #[error("For the animation {animation}, direction {direction} frame {frame}: the {offset} at {pixel:?} is too far from the start of the frame (should only happen on frames greater than 2^16 in width or height)")]
+    OffsetTooLarge {
+        animation: String,
+        direction: usize,
+        frame: usize,
+        offset: OffsetKind,
+        pixel: (u32, u32),
+    },
+    #[error("The frames of the animation {animation} need a size of {needed:?} (with the padding), but the sheet layout has a fixed frame size of {frame_size:?}")]
+    FrameSizeTooSmall {
+        animation: String,
+        needed: (u32, u32),
+        frame_size: (u32, u32),
+    },
+    #[error("Sheets written for SpriteBot can’t have a gutter between frames, as AnimData.xml can’t describe it")]
+    GutterNotSupported,
+    #[error("For the animation {animation}, direction {direction} frame {frame}: a pixel or an offset would be outside of the new canvas")]
+    ContentClipped {
+        animation: String,
+        direction: usize,
+        frame: usize,
+    },
+    #[error("For the animation {animation}, direction {direction} frame {frame}: the new canvas would be too large (max is 2^32 - 1 pixels in width or height, and 65535 for an offset)")]
+    CanvasTooLarge {
+        animation: String,
+        direction: usize,
+        frame: usize,
+    },
 }
@@ -0,0 +1,148 @@
+use std::io::{self, Write};
+
+use vfs::{error::VfsErrorKind, FileSystem, SeekAndRead, SeekAndWrite, VfsPath, VfsResult};
+
+use crate::{write_plan::FileOperation, SpriteBotStorageError};
+
+/// A folder containing the files of a sprite, either the root of a [`FileSystem`] or a [`VfsPath`]
+pub(crate) trait SpriteFolder {
+    /// The full path of a file of this folder
+    fn file_path(&self, file_name: &str) -> String;
+
+    fn open_vfs_file(&self, file_name: &str) -> VfsResult<Box<dyn SeekAndRead + Send>>;
+
+    fn create_vfs_file(&self, file_name: &str) -> VfsResult<Box<dyn SeekAndWrite + Send>>;
+
+    /// Create the folder (and its parents) if it doesn’t exist yet
+    fn create_folder(&self) -> VfsResult<()>;
+
+    /// The name of every file and directory in the folder
+    fn list_vfs_files(&self) -> VfsResult<Vec<String>>;
+
+    fn remove_vfs_file(&self, file_name: &str) -> VfsResult<()>;
+
+    /// Move the file `from` to `to`, which must not exist. File systems that can’t move files copy it instead.
+    fn move_vfs_file(&self, from: &str, to: &str) -> VfsResult<()>;
+
+    fn open_file(
+        &self,
+        file_name: &str,
+    ) -> Result<Box<dyn SeekAndRead + Send>, SpriteBotStorageError> {
+        self.open_vfs_file(file_name)
+            .map_err(|source| SpriteBotStorageError::VfsError {
+                source,
+                path: self.file_path(file_name),
+            })
+    }
+
+    fn remove_file(&self, file_name: &str) -> Result<(), SpriteBotStorageError> {
+        self.remove_vfs_file(file_name)
+            .map_err(|source| SpriteBotStorageError::VfsError {
+                source,
+                path: self.file_path(file_name),
+            })
+    }
+
+    fn move_file(&self, from: &str, to: &str) -> Result<(), SpriteBotStorageError> {
+        self.move_vfs_file(from, to)
+            .map_err(|source| SpriteBotStorageError::VfsError {
+                source,
+                path: self.file_path(from),
+            })
+    }
+
+    fn apply(&self, operation: &FileOperation) -> Result<(), SpriteBotStorageError> {
+        match operation {
+            FileOperation::Write { file_name, content } => self.write_file(file_name, content),
+            FileOperation::Move { from, to } => self.move_file(from, to),
+            FileOperation::Remove { file_name } => self.remove_file(file_name),
+        }
+    }
+
+    /// Create (or replace) the file with the given content
+    fn write_file(&self, file_name: &str, content: &[u8]) -> Result<(), SpriteBotStorageError> {
+        let mut file =
+            self.create_vfs_file(file_name)
+                .map_err(|source| SpriteBotStorageError::VfsError {
+                    source,
+                    path: self.file_path(file_name),
+                })?;
+        file.write_all(content)
+            .map_err(|source| SpriteBotStorageError::WriteFileError {
+                source,
+                path: self.file_path(file_name),
+            })
+    }
+}
+
+/// The root of a file system
+pub(crate) struct RootFolder<'a, T>(pub &'a T);
+
+impl<T: FileSystem> SpriteFolder for RootFolder<'_, T> {
+    fn file_path(&self, file_name: &str) -> String {
+        format!("/{}", file_name)
+    }
+
+    fn open_vfs_file(&self, file_name: &str) -> VfsResult<Box<dyn SeekAndRead + Send>> {
+        self.0.open_file(&self.file_path(file_name))
+    }
+
+    fn create_vfs_file(&self, file_name: &str) -> VfsResult<Box<dyn SeekAndWrite + Send>> {
+        self.0.create_file(&self.file_path(file_name))
+    }
+
+    fn create_folder(&self) -> VfsResult<()> {
+        Ok(())
+    }
+
+    fn list_vfs_files(&self) -> VfsResult<Vec<String>> {
+        Ok(self.0.read_dir("")?.collect())
+    }
+
+    fn remove_vfs_file(&self, file_name: &str) -> VfsResult<()> {
+        self.0.remove_file(&self.file_path(file_name))
+    }
+
+    fn move_vfs_file(&self, from: &str, to: &str) -> VfsResult<()> {
+        let (from, to) = (self.file_path(from), self.file_path(to));
+        match self.0.move_file(&from, &to) {
+            Err(err) if matches!(err.kind(), VfsErrorKind::NotSupported) => {
+                // Like VfsPath::move_file
+                io::copy(&mut self.0.open_file(&from)?, &mut self.0.create_file(&to)?)?;
+                self.0.remove_file(&from)
+            }
+            result => result,
+        }
+    }
+}
+
+impl SpriteFolder for VfsPath {
+    fn file_path(&self, file_name: &str) -> String {
+        format!("{}/{}", self.as_str(), file_name)
+    }
+
+    fn open_vfs_file(&self, file_name: &str) -> VfsResult<Box<dyn SeekAndRead + Send>> {
+        self.join(file_name)?.open_file()
+    }
+
+    fn create_vfs_file(&self, file_name: &str) -> VfsResult<Box<dyn SeekAndWrite + Send>> {
+        self.join(file_name)?.create_file()
+    }
+
+    fn create_folder(&self) -> VfsResult<()> {
+        self.create_dir_all()
+    }
+
+    fn list_vfs_files(&self) -> VfsResult<Vec<String>> {
+        Ok(self.read_dir()?.map(|path| path.filename()).collect())
+    }
+
+    fn remove_vfs_file(&self, file_name: &str) -> VfsResult<()> {
+        self.join(file_name)?.remove_file()
+    }
+
+    fn move_vfs_file(&self, from: &str, to: &str) -> VfsResult<()> {
+        // Fall back to a copy by itself
+        self.join(from)?.move_file(&self.join(to)?)
+    }
+}
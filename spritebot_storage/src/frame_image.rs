@@ -0,0 +1,257 @@
+use std::{fmt::Debug, sync::Arc};
+
+use image::{GenericImageView, Rgba};
+
+use crate::{FrameOffset, RgbaU8};
+
+/// The image of a [`crate::Frame`].
+///
+/// Frames of a loaded sprite point to a rectangle of the sheet they were read from, which is shared by all the frames of the sheet, so loading a sprite doesn’t copy every frame in its own buffer. The pixels are only copied when the frame is modified with [`FrameImage::make_mut`] (copy-on-write).
+///
+/// A shared frame keep the whole sheet in memory, even if it is the last frame using it. Use [`FrameImage::into_image`] to get a standalone image.
+#[derive(Clone)]
+pub struct FrameImage(Storage);
+
+#[derive(Clone)]
+enum Storage {
+    Owned(RgbaU8),
+    Shared {
+        sheet: Arc<RgbaU8>,
+        /// x, y, width, height in the sheet
+        rect: (u32, u32, u32, u32),
+        /// Set if the sheet was loaded from files, and the frame is where it was loaded at
+        origin: Option<Arc<SheetOrigin>>,
+    },
+}
+
+/// The files the sheet of an animation was loaded from, so [`crate::WriteOptions::incremental`] can tell if the animation changed since
+#[derive(Debug)]
+pub(crate) struct SheetOrigin {
+    /// A hash of the content of the files, see `hash_sheets`
+    pub files_hash: u64,
+    pub frame_size: (u32, u32),
+    /// The number of frames per direction and the number of directions
+    pub frame_count: (u32, u32),
+    /// The offsets of every frame as loaded, direction by direction
+    pub offsets: Vec<FrameOffset>,
+}
+
+impl FrameImage {
+    /// A frame that is the rectangle of the sheet starting at (`x`, `y`), without copying it.
+    ///
+    /// Panic if the rectangle isn’t fully inside the sheet, like [`GenericImageView::view`].
+    pub fn from_sheet(sheet: Arc<RgbaU8>, x: u32, y: u32, width: u32, height: u32) -> Self {
+        assert!(
+            x as u64 + width as u64 <= sheet.width() as u64
+                && y as u64 + height as u64 <= sheet.height() as u64,
+            "frame rectangle {:?} out of the {:?} sheet",
+            (x, y, width, height),
+            sheet.dimensions()
+        );
+        Self(Storage::Shared {
+            sheet,
+            rect: (x, y, width, height),
+            origin: None,
+        })
+    }
+
+    /// Like [`FrameImage::from_sheet`], for a frame at its place in a sheet loaded from `origin`
+    pub(crate) fn from_loaded_sheet(
+        sheet: Arc<RgbaU8>,
+        origin: Arc<SheetOrigin>,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let mut image = Self::from_sheet(sheet, x, y, width, height);
+        if let Storage::Shared {
+            origin: image_origin,
+            ..
+        } = &mut image.0
+        {
+            *image_origin = Some(origin);
+        }
+        image
+    }
+
+    /// The files the frame was loaded from, and its position in the sheet, if it is still shared with the loaded sheet at the place it was loaded at
+    pub(crate) fn origin(&self) -> Option<(&Arc<SheetOrigin>, u32, u32)> {
+        match &self.0 {
+            Storage::Shared {
+                origin: Some(origin),
+                rect,
+                ..
+            } => Some((origin, rect.0, rect.1)),
+            _ => None,
+        }
+    }
+
+    /// Return true if the pixels are in a sheet shared with other frames
+    pub fn is_shared(&self) -> bool {
+        matches!(self.0, Storage::Shared { .. })
+    }
+
+    /// Copy the pixels of the frame in a new image
+    pub fn to_image(&self) -> RgbaU8 {
+        match &self.0 {
+            Storage::Owned(image) => image.clone(),
+            Storage::Shared { sheet, rect, .. } => {
+                sheet.view(rect.0, rect.1, rect.2, rect.3).to_image()
+            }
+        }
+    }
+
+    /// Get the pixels of the frame as a standalone image, only copying them if they are shared
+    pub fn into_image(mut self) -> RgbaU8 {
+        std::mem::take(self.make_mut())
+    }
+
+    /// Get mutable access to the pixels. If they are shared, they are first copied, so other frames of the sheet are unaffected.
+    pub fn make_mut(&mut self) -> &mut RgbaU8 {
+        if let Storage::Shared { sheet, rect, .. } = &mut self.0 {
+            let image = if *rect == (0, 0, sheet.width(), sheet.height()) {
+                // Only copied if another frame still use the sheet
+                Arc::unwrap_or_clone(std::mem::take(sheet))
+            } else {
+                sheet.view(rect.0, rect.1, rect.2, rect.3).to_image()
+            };
+            self.0 = Storage::Owned(image);
+        }
+        match &mut self.0 {
+            Storage::Owned(image) => image,
+            Storage::Shared { .. } => unreachable!(),
+        }
+    }
+
+    /// The rectangle of the frame starting at (`x`, `y`). Parts of the rectangle outside of the frame are transparent.
+    ///
+    /// If the frame is shared and the rectangle is inside of it, the result still share the sheet instead of copying the pixels.
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> FrameImage {
+        self.region(x as i64, y as i64, width, height)
+    }
+
+    /// Like [`FrameImage::crop`], but the rectangle may also start before the frame, on the top or left
+    pub(crate) fn region(&self, x: i64, y: i64, width: u32, height: u32) -> FrameImage {
+        let inside = x >= 0
+            && y >= 0
+            && x + width as i64 <= self.width() as i64
+            && y + height as i64 <= self.height() as i64;
+        if let (Storage::Shared { sheet, rect, .. }, true) = (&self.0, inside) {
+            return Self::from_sheet(
+                sheet.clone(),
+                rect.0 + x as u32,
+                rect.1 + y as u32,
+                width,
+                height,
+            );
+        }
+        let mut image = RgbaU8::new(width, height);
+        let raw: &mut [u8] = &mut image;
+        // The part of the rectangle that is inside of the frame, in the coordinates of the frame
+        let (start_x, end_x) = (x.max(0), (x + width as i64).min(self.width() as i64));
+        let (start_y, end_y) = (y.max(0), (y + height as i64).min(self.height() as i64));
+        if start_x < end_x {
+            for source_y in start_y..end_y {
+                let source = &self.row(source_y as u32)[start_x as usize * 4..end_x as usize * 4];
+                let start = ((source_y - y) as usize * width as usize + (start_x - x) as usize) * 4;
+                raw[start..start + source.len()].copy_from_slice(source);
+            }
+        }
+        image.into()
+    }
+
+    /// The smallest rectangle containing every pixel that isn’t fully transparent, as (min x, min y, max x, max y), or `None` if there is no such pixel
+    pub(crate) fn opaque_bounds(&self) -> Option<(u32, u32, u32, u32)> {
+        let mut bounds: Option<(u32, u32, u32, u32)> = None;
+        for y in 0..self.height() {
+            let mut opaque = self
+                .row(y)
+                .chunks_exact(4)
+                .enumerate()
+                .filter(|(_, pixel)| pixel[3] != 0)
+                .map(|(x, _)| x as u32);
+            if let Some(first) = opaque.next() {
+                let last = opaque.next_back().unwrap_or(first);
+                bounds = Some(match bounds {
+                    None => (first, y, last, y),
+                    Some((min_x, min_y, max_x, _)) => (min_x.min(first), min_y, max_x.max(last), y),
+                });
+            }
+        }
+        bounds
+    }
+
+    /// The raw RGBA bytes of a row of the frame
+    fn row(&self, y: u32) -> &[u8] {
+        let (image, (x, sheet_y, width, _)) = match &self.0 {
+            Storage::Owned(image) => (image, (0, 0, image.width(), image.height())),
+            Storage::Shared { sheet, rect, .. } => (sheet.as_ref(), *rect),
+        };
+        let start = (sheet_y + y) as usize * image.width() as usize * 4 + x as usize * 4;
+        &image.as_raw()[start..start + width as usize * 4]
+    }
+}
+
+impl From<RgbaU8> for FrameImage {
+    fn from(image: RgbaU8) -> Self {
+        Self(Storage::Owned(image))
+    }
+}
+
+impl GenericImageView for FrameImage {
+    type Pixel = Rgba<u8>;
+
+    fn dimensions(&self) -> (u32, u32) {
+        match &self.0 {
+            Storage::Owned(image) => image.dimensions(),
+            Storage::Shared { rect, .. } => (rect.2, rect.3),
+        }
+    }
+
+    fn get_pixel(&self, x: u32, y: u32) -> Rgba<u8> {
+        match &self.0 {
+            Storage::Owned(image) => *image.get_pixel(x, y),
+            Storage::Shared { sheet, rect, .. } => {
+                assert!(
+                    x < rect.2 && y < rect.3,
+                    "pixel {:?} out of the {:?} frame",
+                    (x, y),
+                    (rect.2, rect.3)
+                );
+                *sheet.get_pixel(rect.0 + x, rect.1 + y)
+            }
+        }
+    }
+}
+
+/// Two frame images are equal if they have the same size and pixels, whether they are shared or not
+impl PartialEq for FrameImage {
+    fn eq(&self, other: &Self) -> bool {
+        if let (
+            Storage::Shared { sheet, rect, .. },
+            Storage::Shared {
+                sheet: other_sheet,
+                rect: other_rect,
+                ..
+            },
+        ) = (&self.0, &other.0)
+        {
+            if Arc::ptr_eq(sheet, other_sheet) && rect == other_rect {
+                return true;
+            }
+        }
+        self.dimensions() == other.dimensions()
+            && (0..self.height()).all(|y| self.row(y) == other.row(y))
+    }
+}
+
+impl Debug for FrameImage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // The shared sheet would be printed for every frame otherwise
+        f.debug_struct("FrameImage")
+            .field("dimensions", &self.dimensions())
+            .field("shared", &self.is_shared())
+            .finish()
+    }
+}
@@ -0,0 +1,119 @@
+use std::sync::OnceLock;
+
+use vfs::VfsPath;
+
+use crate::{
+    copy_animation, read_animdata, resolve_copy_of, AnimDataXML, Animation, AnimationKind,
+    EncodedAnimation, ExtraMetadata, Sprite, SpriteBotStorageError, SpriteInfo,
+};
+
+/// A sprite whose animations are only read and decoded the first time they are accessed.
+///
+/// AnimData.xml is read when the sprite is opened, and fail like [`SpriteInfo::new`]. The sheets of an animation are read by [`LazySprite::animation`], which cache the result. Errors in the sheets are thus reported when accessing the animation. They aren’t cached, so the next access try again.
+#[derive(Debug)]
+pub struct LazySprite {
+    folder: VfsPath,
+    animdata: AnimDataXML,
+    info: SpriteInfo,
+    /// In the order of AnimData.xml
+    animations: Vec<OnceLock<Animation>>,
+}
+
+impl LazySprite {
+    /// Open the sprite at the root of the given virtual file system
+    pub fn new<T: vfs::FileSystem>(vfs: T) -> Result<Self, SpriteBotStorageError> {
+        Self::new_from_path(VfsPath::new(vfs))
+    }
+
+    /// Open the sprite in the given folder
+    pub fn new_from_path(folder: VfsPath) -> Result<Self, SpriteBotStorageError> {
+        let animdata = read_animdata(&folder)?;
+        let info = SpriteInfo::from_animdata::<VfsPath>(&animdata, None)?;
+        let animations = animdata
+            .anims
+            .anim
+            .iter()
+            .map(|_| OnceLock::new())
+            .collect();
+        Ok(Self {
+            folder,
+            animdata,
+            info,
+            animations,
+        })
+    }
+
+    /// The metadata of the sprite and of its animations, available without loading them
+    pub fn info(&self) -> &SpriteInfo {
+        &self.info
+    }
+
+    pub fn shadow_size(&self) -> u8 {
+        self.animdata.shadow_size
+    }
+
+    /// Content of AnimData.xml outside of the animations that isn’t understood by this library
+    pub fn extra(&self) -> &ExtraMetadata {
+        &self.animdata.extra
+    }
+
+    /// Get the animation of the given kind, loading it if this is the first time it is accessed. Return `Ok(None)` if the sprite doesn’t have it.
+    ///
+    /// A `CopyOf` also load the animation it is a copy of.
+    pub fn animation(
+        &self,
+        kind: AnimationKind,
+    ) -> Result<Option<&Animation>, SpriteBotStorageError> {
+        self.position(&kind)
+            .map(|anim_nb| self.load_animation(anim_nb))
+            .transpose()
+    }
+
+    /// Return true if the animation of the given kind has already been loaded
+    pub fn is_loaded(&self, kind: AnimationKind) -> bool {
+        self.position(&kind)
+            .is_some_and(|anim_nb| self.animations[anim_nb].get().is_some())
+    }
+
+    /// Load every animation that hasn’t been loaded yet, and return the whole sprite like [`Sprite::new_from_path`]
+    pub fn into_sprite(self) -> Result<Sprite, SpriteBotStorageError> {
+        for anim_nb in 0..self.animations.len() {
+            self.load_animation(anim_nb)?;
+        }
+        Ok(Sprite {
+            shadow_size: self.animdata.shadow_size,
+            // All loaded above
+            animations: self
+                .animations
+                .into_iter()
+                .map(|animation| animation.into_inner().unwrap())
+                .collect(),
+            extra: self.animdata.extra,
+        })
+    }
+
+    fn position(&self, kind: &AnimationKind) -> Option<usize> {
+        self.animdata
+            .anims
+            .anim
+            .iter()
+            .position(|anim| anim.name == kind.name())
+    }
+
+    fn load_animation(&self, anim_nb: usize) -> Result<&Animation, SpriteBotStorageError> {
+        if let Some(animation) = self.animations[anim_nb].get() {
+            return Ok(animation);
+        }
+        let anim_sources = &self.animdata.anims.anim;
+        let anim_source = &anim_sources[anim_nb];
+        let animation = match &anim_source.copy_of {
+            Some(copy_of) => {
+                let target = self.load_animation(resolve_copy_of(anim_sources, anim_nb)?)?;
+                copy_animation(target, anim_source, copy_of)
+            }
+            None => EncodedAnimation::read(&self.folder, anim_source)?.decode()?,
+        };
+        // If another thread loaded it in the meantime, its version is kept
+        Ok(self.animations[anim_nb].get_or_init(|| animation))
+    }
+}
@@ -1,91 +1,491 @@
-use std::io::{BufReader, Cursor};
+use std::{
+    fmt::Display,
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{BufReader, Cursor, Read},
+    sync::Arc,
+};
 
 use animdata_xml::AnimsXML;
-use image::{GenericImage, GenericImageView, ImageBuffer, ImageFormat, Rgba};
+use folder::{RootFolder, SpriteFolder};
+use image::{GenericImage, GenericImageView, ImageBuffer, ImageFormat, Pixel, Rgba};
+use vfs::VfsPath;
 
 mod error;
-pub use error::SpriteBotStorageError;
+pub use error::{Axis, SpriteBotStorageError};
 
 mod animdata_xml;
-pub use animdata_xml::AnimDataXML;
+pub use animdata_xml::{AnimDataXML, ExtraMetadata};
+
+mod folder;
+
+mod frame_image;
+pub use frame_image::FrameImage;
+use frame_image::SheetOrigin;
+
+mod sheet_layout;
+pub use sheet_layout::{FrameSizeRule, SheetLayoutOptions};
+
+mod relative_offset;
+pub use relative_offset::RelativeFrameOffset;
+
+mod canvas;
+
+mod parallel;
+use parallel::map_ordered;
+
+mod write_plan;
+use write_plan::WritePlan;
+
+#[cfg(feature = "zip")]
+mod zip_archive;
+
+#[cfg(feature = "async")]
+mod async_io;
+
+mod animation_kind;
+pub use animation_kind::AnimationKind;
+
+mod direction;
+pub use direction::{Direction, DirectionLayout};
+
+mod sprite_info;
+pub use sprite_info::{AnimationInfo, SpriteInfo};
+
+mod lazy_sprite;
+pub use lazy_sprite::LazySprite;
+
+mod validation;
+pub use validation::{
+    IssueLocation, Severity, ValidationIssue, ValidationProblem, ValidationReport,
+    ValidationWarning,
+};
+
+#[cfg(test)]
+mod tests;
 
 use crate::animdata_xml::{AnimXML, DurationsXML};
 
 type RgbaU8 = ImageBuffer<Rgba<u8>, Vec<u8>>;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Sprite {
     pub shadow_size: u8,
     pub animations: Vec<Animation>,
+    /// Content of AnimData.xml outside of the animations that isn’t understood by this library
+    pub extra: ExtraMetadata,
+}
+
+/// An animation that couldn’t be loaded by [`Sprite::new_lenient`]
+#[derive(Debug)]
+pub struct SkippedAnimation {
+    pub name: String,
+    pub error: SpriteBotStorageError,
+}
+
+/// Options controlling how [`Sprite::write_to_folder_with_options`] write a sprite
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    /// Write animations that are identical to a previous one (same frames, offsets, durations and rush/hit/return frames) as a `CopyOf` of it, instead of writing their sheets again.
+    ///
+    /// The first animation (in the order of [`Sprite::animations`]) of a group of identical animations is the one that keep its sheets.
+    pub deduplicate: bool,
+    /// Keep the sheets already in the target folder for animations that weren’t modified since they were loaded from these same sheets, instead of generating and encoding them again. AnimData.xml is always written.
+    ///
+    /// An animation is unmodified if its frames are still the ones of the loaded sheet, at the same place and with the same offsets (see [`FrameImage`]). The sheets in the folder are read, but not decoded, to check they are the ones the animation was loaded from. Animations that were created or modified are always written. It has no effect when writing a zip archive.
+    pub incremental: bool,
+    /// How the frames are placed in the sheets. [`SheetLayoutOptions::gutter`] must be 0.
+    pub layout: SheetLayoutOptions,
+}
+
+/// The three images each animation is made of
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImageKind {
+    Anim,
+    Offsets,
+    Shadow,
+}
+
+impl ImageKind {
+    pub const ALL: [ImageKind; 3] = [ImageKind::Anim, ImageKind::Offsets, ImageKind::Shadow];
+
+    /// The suffix of the image file name, like `Anim` in `Walk-Anim.png`
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Anim => "Anim",
+            Self::Offsets => "Offsets",
+            Self::Shadow => "Shadow",
+        }
+    }
+}
+
+impl Display for ImageKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
 }
 
 fn get_number_of_component_on_axis(
     size: u32,
     divider: u32,
-    side_name: &str,
+    axis: Axis,
     anim_name: &str,
-    image_kind: &str,
+    image_kind: ImageKind,
 ) -> Result<u32, SpriteBotStorageError> {
-    if size.checked_rem(divider as u32).ok_or_else(|| {
-        SpriteBotStorageError::SpriteSizeZero(
-            anim_name.to_string(),
-            image_kind.to_string(),
-            side_name.into(),
-        )
-    })? != 0
+    if size
+        .checked_rem(divider)
+        .ok_or_else(|| SpriteBotStorageError::SpriteSizeZero {
+            animation: anim_name.to_string(),
+            image_kind,
+            axis,
+        })?
+        != 0
     {
-        return Err(SpriteBotStorageError::SpriteSizeNotMultiple(
-            anim_name.to_string(),
-            image_kind.to_string(),
-            side_name.into(),
-        ));
-    }
-    return Ok(size / divider);
-}
-
-/**
- * Read the image for the animation of said type (Anim, Offsets or Shadow) in the VFS, and split it up based on the height and width.
- *
- * The first Vec is for the column, the second is for the row. The row Vecs will always have the same size between them.
- */
-fn get_image<T: vfs::FileSystem>(
-    vfs: &T,
+        return Err(SpriteBotStorageError::SpriteSizeNotMultiple {
+            animation: anim_name.to_string(),
+            image_kind,
+            axis,
+            size,
+            frame_size: divider,
+        });
+    }
+    Ok(size / divider)
+}
+
+/// Read the PNG file of the animation of said type (Anim, Offsets or Shadow) in the folder, without decoding it
+fn read_sheet_file<F: SpriteFolder + ?Sized>(
+    folder: &F,
     name: &str,
-    kind: &str,
-    frame_height: u32,
-    frame_width: u32,
-) -> Result<Vec<Vec<RgbaU8>>, SpriteBotStorageError> {
-    let path = format!("/{}-{}.png", name, kind);
-    let image_file = vfs
-        .open_file(&path)
-        .map_err(|err| SpriteBotStorageError::VfsError(err, path.to_string()))?;
-
-    let img = image::ImageReader::with_format(BufReader::new(image_file), ImageFormat::Png)
-        .decode()
-        .map_err(|err| {
-            SpriteBotStorageError::ErrorImageRead(kind.to_string(), name.to_string(), err)
-        })?
-        .to_rgba8();
-
-    let nb_on_width =
-        get_number_of_component_on_axis(img.width(), frame_width, "width", name, kind)?;
-    let nb_on_height =
-        get_number_of_component_on_axis(img.height(), frame_height, "height", name, kind)?;
-
-    let mut result = Vec::new();
-    for column in 0..nb_on_height {
-        let mut column_result = Vec::new();
-        let column_start = column * frame_height;
-        for row in 0..nb_on_width {
-            let row_start = row * frame_width;
-            column_result.push(
-                img.view(row_start, column_start, frame_width, frame_height)
-                    .to_image(),
-            );
+    kind: ImageKind,
+) -> Result<Vec<u8>, SpriteBotStorageError> {
+    let file_name = sheet_file_name(name, kind);
+    let mut content = Vec::new();
+    folder
+        .open_file(&file_name)?
+        .read_to_end(&mut content)
+        .map_err(|source| SpriteBotStorageError::VfsError {
+            source: source.into(),
+            path: folder.file_path(&file_name),
+        })?;
+    Ok(content)
+}
+
+fn decode_sheet(
+    content: &[u8],
+    name: &str,
+    kind: ImageKind,
+) -> Result<RgbaU8, SpriteBotStorageError> {
+    Ok(
+        image::load_from_memory_with_format(content, ImageFormat::Png)
+            .map_err(|source| SpriteBotStorageError::ErrorImageRead {
+                animation: name.to_string(),
+                image_kind: kind,
+                source,
+            })?
+            .to_rgba8(),
+    )
+}
+
+/// A hash of the PNG files of an animation, to tell if they changed since it was loaded
+fn hash_sheets(sheets: &[Vec<u8>]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    sheets.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn sheet_file_name(name: &str, kind: ImageKind) -> String {
+    format!("{}-{}.png", name, kind)
+}
+
+/// Return true if the file name is the one of a sheet of any animation
+fn is_sheet_file_name(file_name: &str) -> bool {
+    ImageKind::ALL
+        .iter()
+        .any(|kind| file_name.ends_with(&format!("-{}.png", kind)))
+}
+
+const ANIMDATA_FILE_NAME: &str = "AnimData.xml";
+
+fn read_animdata<F: SpriteFolder + ?Sized>(
+    folder: &F,
+) -> Result<AnimDataXML, SpriteBotStorageError> {
+    let animdata_xml_file = folder.open_file(ANIMDATA_FILE_NAME)?;
+    AnimDataXML::from_reader(BufReader::new(animdata_xml_file))
+}
+
+/// An animation that may be a `CopyOf` of another, either an entry of AnimData.xml or an [`Animation`]
+trait CopyOfEntry {
+    fn name(&self) -> &str;
+
+    fn copy_of(&self) -> Option<&str>;
+}
+
+impl CopyOfEntry for AnimXML {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn copy_of(&self) -> Option<&str> {
+        self.copy_of.as_deref()
+    }
+}
+
+impl CopyOfEntry for Animation {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn copy_of(&self) -> Option<&str> {
+        self.copy_of.as_deref()
+    }
+}
+
+/// Follow the chain of `CopyOf` starting at the animation `anim_nb`, and return the position of the first animation that isn’t a `CopyOf`.
+fn resolve_copy_of<T: CopyOfEntry>(
+    anims: &[T],
+    anim_nb: usize,
+) -> Result<usize, SpriteBotStorageError> {
+    let mut current_nb = anim_nb;
+    // A chain can’t be longer than the number of animation without looping
+    for _ in 0..=anims.len() {
+        let current = &anims[current_nb];
+        let copy_of = match current.copy_of() {
+            Some(copy_of) => copy_of,
+            None => return Ok(current_nb),
+        };
+        current_nb = anims
+            .iter()
+            .position(|anim| anim.name() == copy_of)
+            .ok_or_else(|| SpriteBotStorageError::CopyOfNotFound {
+                animation: current.name().to_string(),
+                copy_of: copy_of.to_string(),
+            })?;
+    }
+    Err(SpriteBotStorageError::CopyOfCycle {
+        animation: anims[anim_nb].name().to_string(),
+    })
+}
+
+fn convert_duration(
+    anim_name: &str,
+    frame_nb: usize,
+    duration: usize,
+) -> Result<u32, SpriteBotStorageError> {
+    duration
+        .try_into()
+        .map_err(|source| SpriteBotStorageError::TooLargeDuration {
+            animation: anim_name.to_string(),
+            frame: frame_nb,
+            duration,
+            source,
+        })
+}
+
+/// Get the frame size and the durations of an animation that isn’t a `CopyOf` from its entry in AnimData.xml
+fn read_anim_fields(
+    anim_source: &AnimXML,
+) -> Result<((u32, u32), Vec<u32>), SpriteBotStorageError> {
+    let name = &anim_source.name;
+    let missing_field = |field| SpriteBotStorageError::MissingAnimField {
+        animation: name.clone(),
+        field,
+    };
+    let frame_width = anim_source
+        .frame_width
+        .ok_or_else(|| missing_field("FrameWidth"))?;
+    let frame_height = anim_source
+        .frame_height
+        .ok_or_else(|| missing_field("FrameHeight"))?;
+    let durations = anim_source
+        .durations
+        .as_ref()
+        .ok_or_else(|| missing_field("Durations"))?
+        .duration
+        .iter()
+        .enumerate()
+        .map(|(frame_nb, duration)| convert_duration(name, frame_nb, *duration))
+        .collect::<Result<Vec<u32>, _>>()?;
+    Ok(((frame_width, frame_height), durations))
+}
+
+/// Check the dimensions of the three sheets of an animation (in the order of [`ImageKind::ALL`]) against its frame size and number of durations.
+///
+/// Return the number of frames per direction and the number of directions.
+fn check_sheet_dimensions(
+    name: &str,
+    frame_size: (u32, u32),
+    duration_count: usize,
+    dimensions: [(u32, u32); 3],
+) -> Result<(u32, u32), SpriteBotStorageError> {
+    let [anim_dimensions, offsets_dimensions, shadow_dimensions] = dimensions;
+    for (image_kind, sheet_dimensions) in [
+        (ImageKind::Offsets, offsets_dimensions),
+        (ImageKind::Shadow, shadow_dimensions),
+    ] {
+        if sheet_dimensions != anim_dimensions {
+            return Err(SpriteBotStorageError::SpriteSizeNotIdentical {
+                animation: name.to_string(),
+                image_kind,
+                expected: anim_dimensions,
+                actual: sheet_dimensions,
+            });
         }
-        result.push(column_result);
     }
-    Ok(result)
+
+    let frame_count = get_number_of_component_on_axis(
+        anim_dimensions.0,
+        frame_size.0,
+        Axis::Width,
+        name,
+        ImageKind::Anim,
+    )?;
+    let direction_count = get_number_of_component_on_axis(
+        anim_dimensions.1,
+        frame_size.1,
+        Axis::Height,
+        name,
+        ImageKind::Anim,
+    )?;
+
+    if frame_count as usize != duration_count {
+        return Err(SpriteBotStorageError::InconsistantDuration {
+            animation: name.to_string(),
+            frame_count: frame_count as usize,
+            duration_count,
+        });
+    }
+    Ok((frame_count, direction_count))
+}
+
+/// An animation that isn’t a `CopyOf`, whose sheets have been read but not decoded yet
+struct EncodedAnimation<'a> {
+    source: &'a AnimXML,
+    frame_size: (u32, u32),
+    durations: Vec<u32>,
+    /// The PNG files, in the order of [`ImageKind::ALL`]
+    sheets: Vec<Vec<u8>>,
+}
+
+impl<'a> EncodedAnimation<'a> {
+    /// Check the entry in AnimData.xml and read the sheets of the animation
+    fn read<F: SpriteFolder + ?Sized>(
+        folder: &F,
+        anim_source: &'a AnimXML,
+    ) -> Result<Self, SpriteBotStorageError> {
+        let (frame_size, durations) = read_anim_fields(anim_source)?;
+
+        let sheets = ImageKind::ALL
+            .iter()
+            .map(|kind| read_sheet_file(folder, &anim_source.name, *kind))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            source: anim_source,
+            frame_size,
+            durations,
+            sheets,
+        })
+    }
+
+    /// Decode the sheets and extract the frames, in parallel with the `rayon` feature
+    fn decode(self) -> Result<Animation, SpriteBotStorageError> {
+        let name = &self.source.name;
+        let (frame_width, frame_height) = self.frame_size;
+
+        let mut decoded = map_ordered(
+            ImageKind::ALL.iter().zip(&self.sheets).collect(),
+            |(kind, content)| decode_sheet(content, name, *kind),
+        )
+        .into_iter();
+        // In the order of ImageKind::ALL
+        // Shared by the frames instead of copying each of them
+        let anim_sheet = Arc::new(decoded.next().unwrap()?);
+        let offsets_sheet = decoded.next().unwrap()?;
+        let shadow_sheet = decoded.next().unwrap()?;
+
+        let (frame_count, direction_count) = check_sheet_dimensions(
+            name,
+            self.frame_size,
+            self.durations.len(),
+            [
+                anim_sheet.dimensions(),
+                offsets_sheet.dimensions(),
+                shadow_sheet.dimensions(),
+            ],
+        )?;
+
+        let cells = (0..direction_count)
+            .flat_map(|direction_nb| (0..frame_count).map(move |frame_nb| (direction_nb, frame_nb)))
+            .collect();
+        let offsets = map_ordered(cells, |(direction_nb, frame_nb)| {
+            let (x, y) = (frame_nb * frame_width, direction_nb * frame_height);
+            let location = FrameLocation {
+                animation: name,
+                direction: direction_nb as usize,
+                frame: frame_nb as usize,
+                origin: (x, y),
+            };
+            FrameOffset::from_sheets(
+                &offsets_sheet,
+                &shadow_sheet,
+                (x, y, frame_width, frame_height),
+                &location,
+            )
+        })
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+
+        let origin = Arc::new(SheetOrigin {
+            files_hash: hash_sheets(&self.sheets),
+            frame_size: self.frame_size,
+            frame_count: (frame_count, direction_count),
+            offsets: offsets.clone(),
+        });
+        let mut offsets = offsets.into_iter();
+        let images = (0..direction_count)
+            .map(|direction_nb| {
+                (0..frame_count)
+                    .map(|frame_nb| Frame {
+                        image: FrameImage::from_loaded_sheet(
+                            anim_sheet.clone(),
+                            origin.clone(),
+                            frame_nb * frame_width,
+                            direction_nb * frame_height,
+                            frame_width,
+                            frame_height,
+                        ),
+                        offsets: offsets.next().unwrap(),
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Ok(Animation {
+            name: self.source.name.clone(),
+            index: self.source.index,
+            copy_of: None,
+            rush_frame: self.source.rush_frame,
+            hit_frame: self.source.hit_frame,
+            return_frame: self.source.return_frame,
+            durations: self.durations,
+            images,
+            extra: self.source.extra.clone(),
+        })
+    }
+}
+
+/// The animation described by the `CopyOf` entry `anim_source`, with the frames and timing of `target`
+fn copy_animation(target: &Animation, anim_source: &AnimXML, copy_of: &str) -> Animation {
+    Animation {
+        name: anim_source.name.clone(),
+        index: anim_source.index,
+        copy_of: Some(copy_of.to_string()),
+        rush_frame: target.rush_frame,
+        hit_frame: target.hit_frame,
+        return_frame: target.return_frame,
+        durations: target.durations.clone(),
+        images: target.images.clone(),
+        extra: anim_source.extra.clone(),
+    }
 }
 
 impl Sprite {
@@ -93,201 +493,596 @@ impl Sprite {
         Self {
             shadow_size,
             animations: Vec::new(),
+            extra: ExtraMetadata::default(),
         }
     }
 
     /// Read the sprite contained at the root of the given virtual file system
+    ///
+    /// Animations using `CopyOf` are resolved (following chains of `CopyOf`), and their frames are copied from the animation they refer to.
     pub fn new<T: vfs::FileSystem>(vfs: &T) -> Result<Self, SpriteBotStorageError> {
-        let animdata_xml_file = vfs
-            .open_file("/AnimData.xml")
-            .map_err(|err| SpriteBotStorageError::VfsError(err, "/AnimData.xml".to_string()))?;
-        let animdata_xml: AnimDataXML =
-            quick_xml::de::from_reader(BufReader::new(animdata_xml_file))?;
-        let mut animations = Vec::new();
-
-        for anim_source in &animdata_xml.anims.anim {
-            let mut images = Vec::new();
-
-            let anim_image = get_image(
-                vfs,
-                &anim_source.name,
-                "Anim",
-                anim_source.frame_height,
-                anim_source.frame_width,
-            )?;
-            let shadow_image = get_image(
-                vfs,
-                &anim_source.name,
-                "Shadow",
-                anim_source.frame_height,
-                anim_source.frame_width,
-            )?;
-            let offset_image = get_image(
-                vfs,
-                &anim_source.name,
-                "Offsets",
-                anim_source.frame_height,
-                anim_source.frame_width,
-            )?;
-
-            if anim_image.len() != shadow_image.len() || shadow_image.len() != offset_image.len() {
-                return Err(SpriteBotStorageError::SpriteSizeNotIdentical(
-                    anim_source.name.clone(),
-                ));
-            }
+        Self::load(&RootFolder(vfs), false).map(|(sprite, _)| sprite)
+    }
 
-            for (column_nb, ((column_anims, column_shadows), column_offsets)) in anim_image
-                .into_iter()
-                .zip(&shadow_image)
-                .zip(&offset_image)
-                .enumerate()
-            {
-                if column_anims.len() != column_shadows.len()
-                    || column_shadows.len() != column_offsets.len()
-                {
-                    return Err(SpriteBotStorageError::SpriteSizeNotIdentical(
-                        anim_source.name.clone(),
-                    ));
-                };
+    /// Read the sprite contained in the given folder, like [`Sprite::new`].
+    ///
+    /// This allow to read a sprite in a sub-directory, like `VfsPath::new(PhysicalFS::new("SpriteCollab/sprite")).join("0025")?`.
+    pub fn new_from_path(path: &VfsPath) -> Result<Self, SpriteBotStorageError> {
+        Self::load(path, false).map(|(sprite, _)| sprite)
+    }
 
-                if column_anims.len() != anim_source.durations.duration.len() {
-                    return Err(SpriteBotStorageError::InconsistantDuration(
-                        anim_source.name.clone(),
-                        column_anims.len(),
-                        anim_source.durations.duration.len(),
-                    ));
-                }
+    /// Read the sprite like [`Sprite::new`], but skip the animations that fail to load instead of failing.
+    ///
+    /// The skipped animations are returned along with the error that caused them to be skipped, in the order of AnimData.xml. An animation that is a `CopyOf` of a skipped animation is skipped too. An error is still returned if AnimData.xml itself can’t be read.
+    pub fn new_lenient<T: vfs::FileSystem>(
+        vfs: &T,
+    ) -> Result<(Self, Vec<SkippedAnimation>), SpriteBotStorageError> {
+        Self::load(&RootFolder(vfs), true)
+    }
 
-                let mut frames = Vec::new();
-                for (
-                    row_nb,
-                    (((anim_local_image, shadow_local_image), offset_local_image), duration),
-                ) in column_anims
-                    .into_iter()
-                    .zip(column_shadows)
-                    .zip(column_offsets)
-                    .zip(anim_source.durations.duration.iter().copied())
-                    .enumerate()
-                {
-                    let offsets = FrameOffset::from_images(
-                        &offset_local_image,
-                        &shadow_local_image,
-                        column_nb,
-                        row_nb,
-                        &anim_source.name,
-                    )?;
-
-                    frames.push(Frame {
-                        image: anim_local_image,
-                        offsets,
-                        duration: duration
-                            .try_into()
-                            .map_err(SpriteBotStorageError::TooLargeDuration)?,
-                    })
-                }
+    /// Read the sprite contained in the given folder, like [`Sprite::new_lenient`]
+    pub fn new_lenient_from_path(
+        path: &VfsPath,
+    ) -> Result<(Self, Vec<SkippedAnimation>), SpriteBotStorageError> {
+        Self::load(path, true)
+    }
 
-                images.push(frames);
-            }
+    fn load<F: SpriteFolder>(
+        folder: &F,
+        lenient: bool,
+    ) -> Result<(Self, Vec<SkippedAnimation>), SpriteBotStorageError> {
+        let animdata_xml = read_animdata(folder)?;
+
+        // The folder is read in order, and the sheets are decoded in parallel with the `rayon` feature
+        let encoded = animdata_xml
+            .anims
+            .anim
+            .iter()
+            .map(|anim_source| {
+                anim_source
+                    .copy_of
+                    .is_none()
+                    .then(|| EncodedAnimation::read(folder, anim_source))
+            })
+            .collect();
+        Self::from_encoded(&animdata_xml, encoded, lenient)
+    }
+
+    /// Decode the animations read from a folder, and resolve the `CopyOf`.
+    ///
+    /// `encoded` has an entry for each animation of `animdata_xml`, which is `None` for `CopyOf`.
+    fn from_encoded(
+        animdata_xml: &AnimDataXML,
+        encoded: Vec<Option<Result<EncodedAnimation, SpriteBotStorageError>>>,
+        lenient: bool,
+    ) -> Result<(Self, Vec<SkippedAnimation>), SpriteBotStorageError> {
+        let anim_sources = &animdata_xml.anims.anim;
+        let decoded = map_ordered(encoded, |encoded| {
+            encoded.map(|encoded| encoded.and_then(EncodedAnimation::decode))
+        });
 
-            animations.push(Animation {
-                name: anim_source.name.clone(),
-                index: anim_source.index,
-                rush_frame: anim_source.rush_frame,
-                hit_frame: anim_source.hit_frame,
-                return_frame: anim_source.return_frame,
-                images,
+        let mut animations: Vec<Result<Option<Animation>, SpriteBotStorageError>> = Vec::new();
+        for decoded in decoded {
+            animations.push(match decoded {
+                None => Ok(None),
+                Some(Ok(animation)) => Ok(Some(animation)),
+                Some(Err(err)) if lenient => Err(err),
+                Some(Err(err)) => return Err(err),
             });
         }
 
-        Ok(Self {
-            shadow_size: animdata_xml.shadow_size,
-            animations,
-        })
+        for (anim_nb, anim_source) in anim_sources.iter().enumerate() {
+            if let Some(copy_of) = &anim_source.copy_of {
+                let target_nb = match resolve_copy_of(anim_sources, anim_nb) {
+                    Ok(target_nb) => target_nb,
+                    Err(err) if lenient => {
+                        animations[anim_nb] = Err(err);
+                        continue;
+                    }
+                    Err(err) => return Err(err),
+                };
+                // resolve_copy_of always return a non-CopyOf animation, and those were all loaded (or skipped) above
+                let target = match &animations[target_nb] {
+                    Ok(target) => target.as_ref().unwrap(),
+                    Err(_) => {
+                        animations[anim_nb] = Err(SpriteBotStorageError::CopyOfSkipped {
+                            animation: anim_source.name.clone(),
+                            copy_of: anim_sources[target_nb].name.clone(),
+                        });
+                        continue;
+                    }
+                };
+                animations[anim_nb] = Ok(Some(copy_animation(target, anim_source, copy_of)));
+            }
+        }
+
+        let mut loaded = Vec::new();
+        let mut skipped = Vec::new();
+        for (animation, anim_source) in animations.into_iter().zip(anim_sources) {
+            match animation {
+                Ok(animation) => loaded.push(animation.unwrap()),
+                Err(error) => skipped.push(SkippedAnimation {
+                    name: anim_source.name.clone(),
+                    error,
+                }),
+            }
+        }
+
+        Ok((
+            Self {
+                shadow_size: animdata_xml.shadow_size,
+                animations: loaded,
+                extra: animdata_xml.extra.clone(),
+            },
+            skipped,
+        ))
+    }
+
+    /// Check the sprite contained at the root of the given virtual file system, reporting every problem found instead of stopping at the first error like [`Sprite::new`]
+    pub fn validate<T: vfs::FileSystem>(vfs: &T) -> ValidationReport {
+        validation::validate(&RootFolder(vfs))
+    }
+
+    /// Check the sprite contained in the given folder, like [`Sprite::validate`]
+    pub fn validate_path(path: &VfsPath) -> ValidationReport {
+        validation::validate(path)
     }
 
+    /// Get the animation of the given kind, if this sprite has it
+    pub fn animation(&self, kind: AnimationKind) -> Option<&Animation> {
+        self.animations
+            .iter()
+            .find(|animation| animation.name == kind.name())
+    }
+
+    pub fn animation_mut(&mut self, kind: AnimationKind) -> Option<&mut Animation> {
+        self.animations
+            .iter_mut()
+            .find(|animation| animation.name == kind.name())
+    }
+
+    /// Write the sprite at the root of the given virtual file system, with the default [`WriteOptions`]
     pub fn write_to_folder<T: vfs::FileSystem>(
         &self,
         vfs: &mut T,
     ) -> Result<(), SpriteBotStorageError> {
+        self.write_to_folder_with_options(vfs, &WriteOptions::default())
+    }
+
+    /// Write the sprite at the root of the given virtual file system.
+    ///
+    /// Existing files are only replaced once every sheet has been generated, so a failure doesn’t leave a mix of old and new files. Sheets of animations that aren’t in the sprite are removed.
+    ///
+    /// With [`WriteOptions::incremental`], only the sheets of animations that were modified since they were loaded from this folder are generated and written.
+    pub fn write_to_folder_with_options<T: vfs::FileSystem>(
+        &self,
+        vfs: &mut T,
+        options: &WriteOptions,
+    ) -> Result<(), SpriteBotStorageError> {
+        self.write(&RootFolder(vfs), options)
+    }
+
+    /// Write the sprite in the given folder, creating it if needed
+    pub fn write_to_path(
+        &self,
+        path: &VfsPath,
+        options: &WriteOptions,
+    ) -> Result<(), SpriteBotStorageError> {
+        path.create_folder()
+            .map_err(|source| SpriteBotStorageError::VfsError {
+                source,
+                path: path.as_str().to_string(),
+            })?;
+        self.write(path, options)
+    }
+
+    /// Write the sprite in the folder, only touching the existing files once everything has been generated, see [`WritePlan`].
+    fn write<F: SpriteFolder>(
+        &self,
+        folder: &F,
+        options: &WriteOptions,
+    ) -> Result<(), SpriteBotStorageError> {
+        let files = self.generate_files(options, Some(folder))?;
+        let existing_files =
+            folder
+                .list_vfs_files()
+                .map_err(|source| SpriteBotStorageError::VfsError {
+                    source,
+                    path: folder.file_path(""),
+                })?;
+        WritePlan::new(&files, &existing_files).run(folder)
+    }
+
+    /// Generate the name and content of every file of the sprite, with AnimData.xml last
+    fn generate_files(
+        &self,
+        options: &WriteOptions,
+        folder: Option<&dyn SpriteFolder>,
+    ) -> Result<Vec<GeneratedFile>, SpriteBotStorageError> {
+        if options.layout.gutter != 0 {
+            return Err(SpriteBotStorageError::GutterNotSupported);
+        }
+        // Fail like reading the sprite back would
+        for anim_nb in 0..self.animations.len() {
+            resolve_copy_of(&self.animations, anim_nb)?;
+        }
+
+        // How each animation is written: as a copy of another animation, or with its own sheets (`None`)
+        let mut jobs = Vec::new();
+        // animations whose sheets are written, and that can thus be the target of a deduplicated animation
+        let mut written_animations: Vec<&Animation> = Vec::new();
+        for animation in &self.animations {
+            // A `CopyOf` that was modified is written with its own sheets, so the changes aren’t lost
+            let copy_of = animation.copy_of.as_ref().filter(|copy_of| {
+                self.animations
+                    .iter()
+                    .any(|other| &other.name == *copy_of && other.has_same_content(animation))
+            });
+            let copy_of = match copy_of {
+                Some(copy_of) => Some(copy_of),
+                None if options.deduplicate => written_animations
+                    .iter()
+                    .find(|other| other.has_same_content(animation))
+                    .map(|other| &other.name),
+                None => None,
+            };
+            // The folder is only read here, as it can’t be shared between threads
+            let unchanged = match (copy_of, folder) {
+                (None, Some(folder)) if options.incremental => {
+                    animation.unchanged_frame_size(folder)
+                }
+                _ => None,
+            };
+            if copy_of.is_none() {
+                written_animations.push(animation);
+            }
+            jobs.push((animation, copy_of, unchanged));
+        }
+
+        let generated = map_ordered(jobs, |(animation, copy_of, unchanged)| {
+            animation.generate_files(copy_of, unchanged, &options.layout)
+        });
+
+        let mut files = Vec::new();
         let mut animdata = AnimDataXML {
             shadow_size: self.shadow_size,
             anims: AnimsXML { anim: Vec::new() },
+            extra: self.extra.clone(),
         };
-
-        for animation in &self.animations {
-            let (segment_size, anim_img, offset_img, shadow_img) = animation.generate_sheet()?;
-
-            let write_image =
-                |image: RgbaU8, file_name: String| -> Result<(), SpriteBotStorageError> {
-                    let mut buffer = Vec::new();
-                    image
-                        .write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)
-                        .map_err(|e| {
-                            SpriteBotStorageError::WriteImageError(e, file_name.to_string())
-                        })?;
-                    let mut anim_file = vfs
-                        .create_file(&file_name)
-                        .map_err(|e| SpriteBotStorageError::VfsError(e, file_name.to_string()))?;
-                    anim_file
-                        .write_all(&buffer)
-                        .map_err(|e| SpriteBotStorageError::WriteFileError(e, file_name))?;
-                    Ok(())
-                };
-
-            write_image(anim_img, format!("{}-Anim.png", animation.name))?;
-            write_image(offset_img, format!("{}-Offsets.png", animation.name))?;
-            write_image(shadow_img, format!("{}-Shadow.png", animation.name))?;
-
-            animdata.anims.anim.push(AnimXML {
-                name: animation.name.clone(),
-                index: animation.index,
-                rush_frame: animation.rush_frame,
-                hit_frame: animation.hit_frame,
-                return_frame: animation.return_frame,
-                frame_width: segment_size.0,
-                frame_height: segment_size.1,
-                durations: DurationsXML {
-                    duration: animation
-                        .images
-                        .get(0)
-                        .unwrap_or(&vec![])
-                        .iter()
-                        .map(|x| x.duration as usize)
-                        .collect(),
-                },
-            })
+        for generated in generated {
+            let (anim_xml, anim_files) = generated?;
+            animdata.anims.anim.push(anim_xml);
+            files.extend(anim_files);
         }
 
-        let animdata_str = quick_xml::se::to_string(&animdata)?;
-        let mut animdata_file = vfs
-            .create_file("AnimData.xml")
-            .map_err(|e| SpriteBotStorageError::VfsError(e, "AnimData.xml".to_string()))?;
-        animdata_file
-            .write_all(animdata_str.as_bytes())
-            .map_err(|e| SpriteBotStorageError::WriteFileError(e, "AnimData.xml".to_string()))?;
-        Ok(())
+        files.push(GeneratedFile {
+            name: ANIMDATA_FILE_NAME.to_string(),
+            content: Some(animdata.to_xml_string().into_bytes()),
+        });
+        Ok(files)
     }
 }
 
-#[derive(Debug)]
+/// A file of the sprite, as generated by `Sprite::generate_files`
+struct GeneratedFile {
+    name: String,
+    /// `None` if the file is already in the folder with the right content, and should be kept as is. This only happen with [`WriteOptions::incremental`].
+    content: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone)]
 pub struct Animation {
     pub name: String,
     pub index: u32,
+    /// The name of the animation this one is a copy of, if any.
+    ///
+    /// When reading, the frames of the copied animation are still loaded in `images`. When writing, only a `CopyOf` entry is written if this animation still has the same content as the copied one (see [`Animation::has_same_content`]). Otherwise, its own sheets are written and the `CopyOf` is dropped. Writing fail if the copied animation doesn’t exist or if the `CopyOf` form a loop, like when reading.
+    pub copy_of: Option<String>,
     pub rush_frame: Option<u32>,
     pub hit_frame: Option<u32>,
     pub return_frame: Option<u32>,
+    /// The duration of each frame, in game ticks. They are shared by all the directions.
+    pub durations: Vec<u32>,
+    /// The frames of the animation, with one row for each [`Direction`] (in the order of [`Direction::ALL`]), or a single row used for all directions.
     pub images: Vec<Vec<Frame>>,
+    /// Content of the animation in AnimData.xml that isn’t understood by this library
+    pub extra: ExtraMetadata,
 }
 
 impl Animation {
-    /// The three images are 1. Anim, 2. Offsets 3. Shadow
+    pub fn kind(&self) -> AnimationKind {
+        AnimationKind::from_name(&self.name)
+    }
+
+    /// Check that every direction has one frame per duration
+    pub fn check_frame_count(&self) -> Result<(), SpriteBotStorageError> {
+        for (direction_nb, row) in self.images.iter().enumerate() {
+            if row.len() != self.durations.len() {
+                return Err(SpriteBotStorageError::InconsistentFrameCount {
+                    animation: self.name.clone(),
+                    direction: direction_nb,
+                    frame_count: row.len(),
+                    duration_count: self.durations.len(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// The total duration of the animation, in ticks
+    pub fn total_duration(&self) -> u64 {
+        self.durations.iter().map(|duration| *duration as u64).sum()
+    }
+
+    /// Get the index of the frame shown at the given tick since the start of the animation.
+    ///
+    /// Return `None` once the animation is over. To loop the animation, use `tick % animation.total_duration()`.
+    pub fn frame_at_tick(&self, tick: u64) -> Option<usize> {
+        let mut end_of_frame = 0;
+        for (frame_nb, duration) in self.durations.iter().enumerate() {
+            end_of_frame += *duration as u64;
+            if tick < end_of_frame {
+                return Some(frame_nb);
+            }
+        }
+        None
+    }
+
+    /// Multiply every duration by `factor`, rounding to the nearest tick. A frame always last at least one tick.
+    ///
+    /// Fail if `factor` isn’t a finite number greater than zero.
+    pub fn retime(&mut self, factor: f32) -> Result<(), SpriteBotStorageError> {
+        if !(factor.is_finite() && factor > 0.0) {
+            return Err(SpriteBotStorageError::InvalidRetimeFactor {
+                animation: self.name.clone(),
+                factor,
+            });
+        }
+        self.durations = self
+            .durations
+            .iter()
+            .enumerate()
+            .map(|(frame_nb, duration)| {
+                let retimed = (*duration as f64 * factor as f64).round().max(1.0) as u64;
+                retimed
+                    .try_into()
+                    .map_err(|source| SpriteBotStorageError::TooLargeDuration {
+                        animation: self.name.clone(),
+                        frame: frame_nb,
+                        duration: retimed as usize,
+                        source,
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(())
+    }
+
+    /// Whether the animation has a single row of frames or one per direction. `None` if it has any other number of rows.
+    pub fn direction_layout(&self) -> Option<DirectionLayout> {
+        match self.images.len() {
+            1 => Some(DirectionLayout::Single),
+            8 => Some(DirectionLayout::Eight),
+            _ => None,
+        }
+    }
+
+    /// The frames to show when facing the given direction. Animations with a single direction use the same frames for every direction.
+    pub fn frames(&self, direction: Direction) -> Option<&[Frame]> {
+        if self.images.len() == 1 {
+            Some(&self.images[0])
+        } else {
+            self.images.get(direction.index()).map(|row| row.as_slice())
+        }
+    }
+
+    /// The frames to change for the given direction. Animations with a single direction have a single row shared by every direction, so changing it change the frames of all the directions.
+    pub fn frames_mut(&mut self, direction: Direction) -> Option<&mut [Frame]> {
+        if self.images.len() == 1 {
+            Some(&mut self.images[0])
+        } else {
+            self.images
+                .get_mut(direction.index())
+                .map(|row| row.as_mut_slice())
+        }
+    }
+
+    /// Crop every frame to the smallest size that still contain all the pixels that aren’t fully transparent and all the offsets, of every frame of every direction. The offsets are moved to match.
+    ///
+    /// All the frames are cropped the same way, so their content and offsets stay at the same position relative to each other and to the center. Note that sheets are still written with frames of at least 8×8 pixels by default, see [`SheetLayoutOptions::min_frame_size`].
+    ///
+    /// Return the rectangle that was kept, as (x, y, width, height) in the previous frames, or `None` if the animation has no frame.
+    pub fn trim(&mut self) -> Option<(u32, u32, u32, u32)> {
+        // min x, min y, max x, max y
+        let mut bounds: Option<(u32, u32, u32, u32)> = None;
+        let mut include = |area: (u32, u32, u32, u32)| {
+            bounds = Some(match bounds {
+                None => area,
+                Some(bounds) => (
+                    bounds.0.min(area.0),
+                    bounds.1.min(area.1),
+                    bounds.2.max(area.2),
+                    bounds.3.max(area.3),
+                ),
+            });
+        };
+        for frame in self.images.iter().flatten() {
+            if let Some(opaque) = frame.image.opaque_bounds() {
+                include(opaque);
+            }
+            for (x, y) in frame.offsets.positions() {
+                let (x, y) = (x as u32, y as u32);
+                include((x, y, x, y));
+            }
+        }
+        let (min_x, min_y, max_x, max_y) = bounds?;
+        let (width, height) = (max_x - min_x + 1, max_y - min_y + 1);
+
+        for frame in self.images.iter_mut().flatten() {
+            frame.image = frame.image.crop(min_x, min_y, width, height);
+            for position in frame.offsets.positions_mut() {
+                // Every offset is in the bounds, so they are after (min_x, min_y)
+                position.0 -= min_x as u16;
+                position.1 -= min_y as u16;
+            }
+        }
+        Some((min_x, min_y, width, height))
+    }
+
+    /// Generate the AnimData.xml entry and the sheets of this animation, written as a copy of `copy_of` if any.
+    ///
+    /// `unchanged` is the size of the frames in the sheets already in the folder, if this animation wasn’t modified since it was loaded from them (see [`Animation::unchanged_frame_size`]). They are then kept as is if they would be laid out the same way.
+    fn generate_files(
+        &self,
+        copy_of: Option<&String>,
+        unchanged: Option<(u32, u32)>,
+        layout: &SheetLayoutOptions,
+    ) -> Result<(AnimXML, Vec<GeneratedFile>), SpriteBotStorageError> {
+        if let Some(copy_of) = copy_of {
+            let anim_xml = AnimXML {
+                name: self.name.clone(),
+                index: self.index,
+                copy_of: Some(copy_of.clone()),
+                rush_frame: None,
+                hit_frame: None,
+                return_frame: None,
+                frame_width: None,
+                frame_height: None,
+                durations: None,
+                extra: self.extra.clone(),
+            };
+            return Ok((anim_xml, Vec::new()));
+        }
+
+        // With a padding, the frames would be moved compared to the previous sheets
+        let unchanged = unchanged.filter(|frame_size| {
+            layout.padding == 0 && self.sheet_frame_size(layout).ok() == Some(*frame_size)
+        });
+        let (segment_size, files) = match unchanged {
+            Some(segment_size) => {
+                self.check_frame_count()?;
+                let files = ImageKind::ALL
+                    .iter()
+                    .map(|kind| GeneratedFile {
+                        name: sheet_file_name(&self.name, *kind),
+                        content: None,
+                    })
+                    .collect();
+                (segment_size, files)
+            }
+            None => {
+                let (segment_size, anim_img, offset_img, shadow_img) =
+                    self.generate_sheet_with_layout(layout)?;
+                let files = map_ordered(
+                    vec![
+                        (anim_img, ImageKind::Anim),
+                        (offset_img, ImageKind::Offsets),
+                        (shadow_img, ImageKind::Shadow),
+                    ],
+                    |(image, kind)| {
+                        let file_name = sheet_file_name(&self.name, kind);
+                        let mut buffer = Vec::new();
+                        image
+                            .write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)
+                            .map_err(|source| SpriteBotStorageError::WriteImageError {
+                                source,
+                                path: file_name.clone(),
+                            })?;
+                        Ok(GeneratedFile {
+                            name: file_name,
+                            content: Some(buffer),
+                        })
+                    },
+                )
+                .into_iter()
+                .collect::<Result<Vec<_>, SpriteBotStorageError>>()?;
+                (segment_size, files)
+            }
+        };
+
+        let anim_xml = AnimXML {
+            name: self.name.clone(),
+            index: self.index,
+            copy_of: None,
+            rush_frame: self.rush_frame,
+            hit_frame: self.hit_frame,
+            return_frame: self.return_frame,
+            frame_width: Some(segment_size.0),
+            frame_height: Some(segment_size.1),
+            durations: Some(DurationsXML {
+                duration: self
+                    .durations
+                    .iter()
+                    .map(|duration| *duration as usize)
+                    .collect(),
+            }),
+            extra: self.extra.clone(),
+        };
+        Ok((anim_xml, files))
+    }
+
+    /// The files this animation was loaded from, if every frame is still the one that was loaded at this place of the sheet, with the same offsets
+    pub(crate) fn unchanged_origin(&self) -> Option<&SheetOrigin> {
+        let (origin, _, _) = self.images.first()?.first()?.image.origin()?;
+        let (frame_count, direction_count) = origin.frame_count;
+        let (frame_width, frame_height) = origin.frame_size;
+        if self.images.len() != direction_count as usize {
+            return None;
+        }
+        for (direction_nb, line) in self.images.iter().enumerate() {
+            if line.len() != frame_count as usize {
+                return None;
+            }
+            for (frame_nb, frame) in line.iter().enumerate() {
+                let (frame_origin, x, y) = frame.image.origin()?;
+                if !Arc::ptr_eq(origin, frame_origin)
+                    || (x, y)
+                        != (
+                            frame_nb as u32 * frame_width,
+                            direction_nb as u32 * frame_height,
+                        )
+                    || frame.image.dimensions() != origin.frame_size
+                    || frame.offsets
+                        != origin.offsets[direction_nb * frame_count as usize + frame_nb]
+                {
+                    return None;
+                }
+            }
+        }
+        Some(origin)
+    }
+
+    /// The size of the frames in the sheets of this animation in `folder`, if they are the files it was loaded from and it wasn’t modified since. The sheets are read to check it, but not decoded.
+    fn unchanged_frame_size(&self, folder: &dyn SpriteFolder) -> Option<(u32, u32)> {
+        let origin = self.unchanged_origin()?;
+        let sheets = ImageKind::ALL
+            .iter()
+            .map(|kind| read_sheet_file(folder, &self.name, *kind).ok())
+            .collect::<Option<Vec<_>>>()?;
+        (hash_sheets(&sheets) == origin.files_hash).then_some(origin.frame_size)
+    }
+
+    /// Return true if both animations would be written with identical sheets and timing, ignoring their name, index and `CopyOf`.
+    pub fn has_same_content(&self, other: &Animation) -> bool {
+        self.rush_frame == other.rush_frame
+            && self.hit_frame == other.hit_frame
+            && self.return_frame == other.return_frame
+            && self.durations == other.durations
+            && self.images == other.images
+    }
+
+    /// The three images are 1. Anim, 2. Offsets 3. Shadow, with the default [`SheetLayoutOptions`]
+    ///
+    /// Fail if a direction doesn’t have exactly one frame per duration, as that can’t be represented in a sheet.
     pub fn generate_sheet(
         &self,
     ) -> Result<((u32, u32), RgbaU8, RgbaU8, RgbaU8), SpriteBotStorageError> {
-        let mut max_size = (8, 8);
-        let mut max_row = 1;
+        self.generate_sheet_with_layout(&SheetLayoutOptions::default())
+    }
+
+    /// The size needed by the largest frame image and offsets
+    fn content_size(&self) -> (u32, u32) {
+        let mut max_size = (0, 0);
         for line in &self.images {
-            max_row = max_row.max(line.len());
             for row in line {
                 fn max_size_offset(first: (u32, u32), offset: (u16, u16)) -> (u32, u32) {
                     (
@@ -306,24 +1101,57 @@ impl Animation {
                 max_size = max_size_offset(max_size, row.offsets.shadow);
             }
         }
+        max_size
+    }
+
+    /// The size of the frames in a sheet generated with this layout
+    fn sheet_frame_size(
+        &self,
+        layout: &SheetLayoutOptions,
+    ) -> Result<(u32, u32), SpriteBotStorageError> {
+        layout.frame_size(self.content_size(), &self.name)
+    }
+
+    /// Generate the three sheets like [`Animation::generate_sheet`], placing the frames as described by `layout`. The returned size is the one of a frame, without the gutter.
+    pub fn generate_sheet_with_layout(
+        &self,
+        layout: &SheetLayoutOptions,
+    ) -> Result<((u32, u32), RgbaU8, RgbaU8, RgbaU8), SpriteBotStorageError> {
+        self.check_frame_count()?;
+        let frame_size = self.sheet_frame_size(layout)?;
+        let max_row = self
+            .images
+            .iter()
+            .map(|line| line.len())
+            .max()
+            .unwrap_or(0)
+            .max(1);
 
+        let too_large_sheet = |source| SpriteBotStorageError::TooLargeGeneratedSheet {
+            animation: self.name.clone(),
+            source,
+        };
+        // The gutter is only between frames
+        let sheet_length =
+            |count: usize, frame_length: u32| -> Result<u32, SpriteBotStorageError> {
+                let count = count as u64;
+                (count * frame_length as u64 + count.saturating_sub(1) * layout.gutter as u64)
+                    .try_into()
+                    .map_err(too_large_sheet)
+            };
         let image_dimension = (
-            max_size.0
-                * TryInto::<u32>::try_into(max_row)
-                    .map_err(SpriteBotStorageError::TooLargeGeneratedSheet)?,
-            max_size.1
-                * TryInto::<u32>::try_into(self.images.len())
-                    .map_err(SpriteBotStorageError::TooLargeGeneratedSheet)?,
+            sheet_length(max_row, frame_size.0)?,
+            sheet_length(self.images.len(), frame_size.1)?,
         );
 
         let mut anim_image = RgbaU8::new(image_dimension.0, image_dimension.1);
         let mut offset_images = RgbaU8::new(image_dimension.0, image_dimension.1);
         let mut shadow_image = RgbaU8::new(image_dimension.0, image_dimension.1);
 
-        let mut start_x = 0;
-        let mut start_y = 0;
-        for line in &self.images {
-            for row in line {
+        let mut start_y = layout.padding;
+        for (direction_nb, line) in self.images.iter().enumerate() {
+            let mut start_x = layout.padding;
+            for (frame_nb, row) in line.iter().enumerate() {
                 anim_image.copy_from(&row.image, start_x, start_y).unwrap(); // Should never fail
                 shadow_image.put_pixel(
                     start_x + row.offsets.shadow.0 as u32,
@@ -333,7 +1161,12 @@ impl Animation {
                 if row.offsets.head == row.offsets.hand_left
                     || row.offsets.head == row.offsets.hand_right
                 {
-                    return Err(SpriteBotStorageError::InvalidHeadPosition);
+                    return Err(SpriteBotStorageError::InvalidHeadPosition {
+                        animation: self.name.clone(),
+                        direction: direction_nb,
+                        frame: frame_nb,
+                        head: row.offsets.head,
+                    });
                 }
                 offset_images.put_pixel(
                     start_x + row.offsets.head.0 as u32,
@@ -364,24 +1197,23 @@ impl Animation {
                     hand_left_pixel[0] = 255;
                     hand_left_pixel[3] = 255;
                 }
-                start_x += max_size.0;
+                start_x += frame_size.0 + layout.gutter;
             }
-            start_x = 0;
-            start_y += max_size.1;
+            start_y += frame_size.1 + layout.gutter;
         }
 
-        Ok((max_size, anim_image, offset_images, shadow_image))
+        Ok((frame_size, anim_image, offset_images, shadow_image))
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Frame {
-    pub duration: u8,
-    pub image: RgbaU8,
+    /// The pixels of the frame. Frames of a loaded sprite share the pixels of their sheet until modified, see [`FrameImage`].
+    pub image: FrameImage,
     pub offsets: FrameOffset,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FrameOffset {
     pub head: (u16, u16),
     pub hand_left: (u16, u16),
@@ -390,105 +1222,249 @@ pub struct FrameOffset {
     pub shadow: (u16, u16),
 }
 
-fn find_pixel_in_image<T: Fn(&Rgba<u8>) -> bool>(
-    image: &RgbaU8,
-    filter: T,
-    anim_name: &str,
-    column_nb: usize,
-    row_nb: usize,
-    image_kind: &str,
-    color_text: &str,
-) -> Result<(u16, u16), SpriteBotStorageError> {
-    let mut result = None;
-    for (x, y, pixel) in image.enumerate_pixels() {
-        if filter(pixel) {
-            if result.is_some() {
-                return Err(SpriteBotStorageError::ColorDuplicateInPixelDate(
-                    anim_name.into(),
-                    column_nb,
-                    row_nb,
-                    image_kind.into(),
-                    color_text.into(),
-                ));
+/// The offsets stored for each frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OffsetKind {
+    Head,
+    HandLeft,
+    Center,
+    HandRight,
+    Shadow,
+}
+
+impl OffsetKind {
+    /// The colour of the pixel marking this offset in the Offsets or Shadow image
+    pub fn color_name(self) -> &'static str {
+        match self {
+            Self::Head => "black",
+            Self::HandLeft => "red",
+            Self::Center => "green",
+            Self::HandRight => "blue",
+            Self::Shadow => "white",
+        }
+    }
+
+    /// The image this offset is stored in
+    pub fn image_kind(self) -> ImageKind {
+        match self {
+            Self::Shadow => ImageKind::Shadow,
+            _ => ImageKind::Offsets,
+        }
+    }
+}
+
+impl Display for OffsetKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Head => "head",
+            Self::HandLeft => "left hand",
+            Self::Center => "center",
+            Self::HandRight => "right hand",
+            Self::Shadow => "shadow",
+        })
+    }
+}
+
+/// A colour marking an offset in the offsets or shadow image
+#[derive(Clone, Copy)]
+struct OffsetMarker {
+    offset: OffsetKind,
+    filter: fn(&Rgba<u8>) -> bool,
+    /// If true, a frame may not have this marker
+    optional: bool,
+}
+
+const HEAD_MARKER: OffsetMarker = OffsetMarker {
+    offset: OffsetKind::Head,
+    filter: |p| p == &Rgba::from([0, 0, 0, 255]),
+    optional: true,
+};
+const HAND_LEFT_MARKER: OffsetMarker = OffsetMarker {
+    offset: OffsetKind::HandLeft,
+    filter: |p| p.0[0] == 255 && p.0[3] == 255,
+    optional: false,
+};
+const CENTER_MARKER: OffsetMarker = OffsetMarker {
+    offset: OffsetKind::Center,
+    filter: |p| p.0[1] == 255 && p.0[3] == 255,
+    optional: false,
+};
+const HAND_RIGHT_MARKER: OffsetMarker = OffsetMarker {
+    offset: OffsetKind::HandRight,
+    filter: |p| p.0[2] == 255 && p.0[3] == 255,
+    optional: false,
+};
+const SHADOW_MARKER: OffsetMarker = OffsetMarker {
+    offset: OffsetKind::Shadow,
+    filter: |p| p.0 == [255, 255, 255, 255],
+    optional: false,
+};
+
+/// The markers of the offsets image, in the order they are checked
+const OFFSETS_MARKERS: [OffsetMarker; 4] = [
+    HEAD_MARKER,
+    HAND_LEFT_MARKER,
+    CENTER_MARKER,
+    HAND_RIGHT_MARKER,
+];
+
+/// Find the pixels of every marker in a frame of a sheet, in a single pass over it.
+///
+/// `rect` is the frame in the sheet (x, y, width, height). Positions are relative to the frame, in the order of the rows.
+fn scan_markers(
+    sheet: &RgbaU8,
+    rect: (u32, u32, u32, u32),
+    markers: &[OffsetMarker],
+) -> Vec<Vec<(u32, u32)>> {
+    let (start_x, start_y, width, height) = rect;
+    let mut found = vec![Vec::new(); markers.len()];
+    let sheet_row_len = sheet.width() as usize * 4;
+    for y in 0..height {
+        let row_start = (start_y + y) as usize * sheet_row_len + start_x as usize * 4;
+        let row = &sheet.as_raw()[row_start..row_start + width as usize * 4];
+        for (x, pixel) in row.chunks_exact(4).enumerate() {
+            // All the markers are opaque, and most pixels are transparent
+            if pixel[3] != 255 {
+                continue;
+            }
+            let pixel = Rgba::from_slice(pixel);
+            for (marker, found) in markers.iter().zip(&mut found) {
+                if (marker.filter)(pixel) {
+                    found.push((x as u32, y));
+                }
             }
-            result = Some((x, y));
         }
     }
-    if let Some(r) = result {
-        Ok((
-            r.0.try_into()
-                .map_err(|_| SpriteBotStorageError::OffsetTooLarge)?,
-            r.1.try_into()
-                .map_err(|_| SpriteBotStorageError::OffsetTooLarge)?,
-        ))
-    } else {
-        Err(SpriteBotStorageError::ColorNotFoundInPixelData(
-            anim_name.into(),
-            column_nb,
-            row_nb,
-            image_kind.into(),
-            color_text.into(),
-        ))
+    found
+}
+
+/// Where a frame is, to report errors
+struct FrameLocation<'a> {
+    animation: &'a str,
+    direction: usize,
+    frame: usize,
+    /// Position of the top-left corner of the frame in the sheet
+    origin: (u32, u32),
+}
+
+/// Get the only pixel of the marker in the frame, given all the pixels of its colour. Errors report pixels in the coordinates of the whole sheet.
+fn marker_position(
+    pixels: &[(u32, u32)],
+    marker: OffsetMarker,
+    location: &FrameLocation,
+) -> Result<(u16, u16), SpriteBotStorageError> {
+    let to_sheet = |pixel: &(u32, u32)| (location.origin.0 + pixel.0, location.origin.1 + pixel.1);
+    match pixels {
+        [] => Err(SpriteBotStorageError::ColorNotFoundInPixelData {
+            animation: location.animation.into(),
+            direction: location.direction,
+            frame: location.frame,
+            image_kind: marker.offset.image_kind(),
+            offset: marker.offset,
+        }),
+        [r] => {
+            let too_large = |_| SpriteBotStorageError::OffsetTooLarge {
+                animation: location.animation.into(),
+                direction: location.direction,
+                frame: location.frame,
+                offset: marker.offset,
+                pixel: to_sheet(r),
+            };
+            Ok((
+                r.0.try_into().map_err(too_large)?,
+                r.1.try_into().map_err(too_large)?,
+            ))
+        }
+        pixels => Err(SpriteBotStorageError::ColorDuplicateInPixelDate {
+            animation: location.animation.into(),
+            direction: location.direction,
+            frame: location.frame,
+            image_kind: marker.offset.image_kind(),
+            offset: marker.offset,
+            pixels: pixels.iter().map(to_sheet).collect(),
+        }),
     }
 }
 
 impl FrameOffset {
+    /// Every offset, in no particular order
+    fn positions(&self) -> [(u16, u16); 5] {
+        [
+            self.head,
+            self.hand_left,
+            self.hand_right,
+            self.center,
+            self.shadow,
+        ]
+    }
+
+    /// Every offset, to change all of them at once
+    fn positions_mut(&mut self) -> [&mut (u16, u16); 5] {
+        [
+            &mut self.head,
+            &mut self.hand_left,
+            &mut self.hand_right,
+            &mut self.center,
+            &mut self.shadow,
+        ]
+    }
+
     pub fn from_images(
         offset_image: &RgbaU8,
         shadow_image: &RgbaU8,
-        column_nb: usize,
-        row_nb: usize,
+        direction_nb: usize,
+        frame_nb: usize,
         animation_name: &str,
     ) -> Result<Self, SpriteBotStorageError> {
-        let black_offset = match find_pixel_in_image(
+        let location = FrameLocation {
+            animation: animation_name,
+            direction: direction_nb,
+            frame: frame_nb,
+            origin: (
+                frame_nb as u32 * offset_image.width(),
+                direction_nb as u32 * offset_image.height(),
+            ),
+        };
+        let offsets_pixels = scan_markers(
             offset_image,
-            |p| p == &Rgba::from([0, 0, 0, 255]),
-            animation_name,
-            column_nb,
-            row_nb,
-            "offsets",
-            "black",
-        ) {
+            (0, 0, offset_image.width(), offset_image.height()),
+            &OFFSETS_MARKERS,
+        );
+        let shadow_pixels = scan_markers(
+            shadow_image,
+            (0, 0, shadow_image.width(), shadow_image.height()),
+            &[SHADOW_MARKER],
+        );
+        Self::from_marker_pixels(&offsets_pixels, &shadow_pixels[0], &location)
+    }
+
+    /// Read the offsets of the frame at `rect` (x, y, width, height) directly in the sheets
+    fn from_sheets(
+        offsets_sheet: &RgbaU8,
+        shadow_sheet: &RgbaU8,
+        rect: (u32, u32, u32, u32),
+        location: &FrameLocation,
+    ) -> Result<Self, SpriteBotStorageError> {
+        let offsets_pixels = scan_markers(offsets_sheet, rect, &OFFSETS_MARKERS);
+        let shadow_pixels = scan_markers(shadow_sheet, rect, &[SHADOW_MARKER]);
+        Self::from_marker_pixels(&offsets_pixels, &shadow_pixels[0], location)
+    }
+
+    /// `offsets_pixels` are the pixels found for each of [`OFFSETS_MARKERS`]
+    fn from_marker_pixels(
+        offsets_pixels: &[Vec<(u32, u32)>],
+        shadow_pixels: &[(u32, u32)],
+        location: &FrameLocation,
+    ) -> Result<Self, SpriteBotStorageError> {
+        let black_offset = match marker_position(&offsets_pixels[0], HEAD_MARKER, location) {
             Ok(r) => Some(r),
-            Err(SpriteBotStorageError::ColorNotFoundInPixelData(_, _, _, _, _)) => None,
+            Err(SpriteBotStorageError::ColorNotFoundInPixelData { .. }) => None,
             Err(x) => return Err(x),
         };
-        let red_offset = find_pixel_in_image(
-            offset_image,
-            |p| p.0[0] == 255 && p.0[3] == 255,
-            animation_name,
-            column_nb,
-            row_nb,
-            "offsets",
-            "red",
-        )?;
-        let green_offset = find_pixel_in_image(
-            offset_image,
-            |p| p.0[1] == 255 && p.0[3] == 255,
-            animation_name,
-            column_nb,
-            row_nb,
-            "offsets",
-            "green",
-        )?;
-        let blue_offset = find_pixel_in_image(
-            offset_image,
-            |p| p.0[2] == 255 && p.0[3] == 255,
-            animation_name,
-            column_nb,
-            row_nb,
-            "offsets",
-            "blue",
-        )?;
-        let shadow_center = find_pixel_in_image(
-            shadow_image,
-            |p| p.0 == [255, 255, 255, 255],
-            animation_name,
-            column_nb,
-            row_nb,
-            "shadows",
-            "white",
-        )?;
+        let red_offset = marker_position(&offsets_pixels[1], HAND_LEFT_MARKER, location)?;
+        let green_offset = marker_position(&offsets_pixels[2], CENTER_MARKER, location)?;
+        let blue_offset = marker_position(&offsets_pixels[3], HAND_RIGHT_MARKER, location)?;
+        let shadow_center = marker_position(shadow_pixels, SHADOW_MARKER, location)?;
         Ok(FrameOffset {
             head: black_offset.unwrap_or(green_offset),
             center: green_offset,
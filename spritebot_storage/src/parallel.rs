@@ -0,0 +1,22 @@
+/// Apply `f` to every item, and return the results in the order of the items.
+///
+/// With the `rayon` feature, the items are processed in parallel.
+#[cfg(feature = "rayon")]
+pub(crate) fn map_ordered<T: Send, R: Send>(
+    items: Vec<T>,
+    f: impl Fn(T) -> R + Sync + Send,
+) -> Vec<R> {
+    use rayon::prelude::*;
+    items.into_par_iter().map(f).collect()
+}
+
+/// Apply `f` to every item, and return the results in the order of the items.
+///
+/// With the `rayon` feature, the items are processed in parallel.
+#[cfg(not(feature = "rayon"))]
+pub(crate) fn map_ordered<T: Send, R: Send>(
+    items: Vec<T>,
+    f: impl Fn(T) -> R + Sync + Send,
+) -> Vec<R> {
+    items.into_iter().map(f).collect()
+}
@@ -0,0 +1,170 @@
+use crate::FrameOffset;
+
+/// The offsets of a frame as signed vectors from an anchor point, like the center offset or the middle of the frame, instead of positions in the frame like [`FrameOffset`].
+///
+/// They don’t change when the frame is padded or cropped around the anchor. Convert them back with [`RelativeFrameOffset::to_absolute`] or [`RelativeFrameOffset::to_absolute_in_frame`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RelativeFrameOffset {
+    pub head: (i32, i32),
+    pub hand_left: (i32, i32),
+    pub hand_right: (i32, i32),
+    pub center: (i32, i32),
+    pub shadow: (i32, i32),
+}
+
+/// The middle of a frame of the given size
+fn frame_middle(frame_size: (u32, u32)) -> (u32, u32) {
+    (frame_size.0 / 2, frame_size.1 / 2)
+}
+
+impl FrameOffset {
+    /// The offsets relative to the center offset, which is thus always (0, 0)
+    pub fn relative_to_center(&self) -> RelativeFrameOffset {
+        self.relative_to((self.center.0 as u32, self.center.1 as u32))
+    }
+
+    /// The offsets relative to the middle of a frame of the given size, at (width / 2, height / 2)
+    pub fn relative_to_middle(&self, frame_size: (u32, u32)) -> RelativeFrameOffset {
+        self.relative_to(frame_middle(frame_size))
+    }
+
+    /// `anchor` is either an offset or the middle of a frame, so the vectors fit in a i32
+    fn relative_to(&self, anchor: (u32, u32)) -> RelativeFrameOffset {
+        let relative = |position: (u16, u16)| {
+            (
+                (position.0 as i64 - anchor.0 as i64) as i32,
+                (position.1 as i64 - anchor.1 as i64) as i32,
+            )
+        };
+        RelativeFrameOffset {
+            head: relative(self.head),
+            hand_left: relative(self.hand_left),
+            hand_right: relative(self.hand_right),
+            center: relative(self.center),
+            shadow: relative(self.shadow),
+        }
+    }
+
+    /// Move every offset by `delta`, like when pixels are added (positive) or removed (negative) on the top or left of the frame.
+    ///
+    /// Return `None` if an offset would be negative or more than 65535.
+    pub fn translated(&self, delta: (i32, i32)) -> Option<FrameOffset> {
+        self.relative_to((0, 0))
+            .to_absolute_checked(delta.0 as i64, delta.1 as i64, None)
+    }
+}
+
+impl RelativeFrameOffset {
+    /// The offsets as positions in a frame where the anchor is at `anchor`.
+    ///
+    /// Return `None` if an offset would be negative or more than 65535.
+    pub fn to_absolute(self, anchor: (u32, u32)) -> Option<FrameOffset> {
+        self.to_absolute_checked(anchor.0 as i64, anchor.1 as i64, None)
+    }
+
+    /// The offsets as positions in a frame of the given size, with the anchor at its middle, like [`FrameOffset::relative_to_middle`].
+    ///
+    /// Return `None` if an offset would be outside of the frame.
+    pub fn to_absolute_in_frame(self, frame_size: (u32, u32)) -> Option<FrameOffset> {
+        let anchor = frame_middle(frame_size);
+        self.to_absolute_checked(anchor.0 as i64, anchor.1 as i64, Some(frame_size))
+    }
+
+    fn to_absolute_checked(
+        self,
+        anchor_x: i64,
+        anchor_y: i64,
+        frame_size: Option<(u32, u32)>,
+    ) -> Option<FrameOffset> {
+        let (max_x, max_y) = match frame_size {
+            Some((width, height)) => (width as i64 - 1, height as i64 - 1),
+            None => (u16::MAX as i64, u16::MAX as i64),
+        };
+        let absolute = |vector: (i32, i32)| -> Option<(u16, u16)> {
+            let (x, y) = (anchor_x + vector.0 as i64, anchor_y + vector.1 as i64);
+            if x < 0 || y < 0 || x > max_x || y > max_y {
+                return None;
+            }
+            Some((x.try_into().ok()?, y.try_into().ok()?))
+        };
+        Some(FrameOffset {
+            head: absolute(self.head)?,
+            hand_left: absolute(self.hand_left)?,
+            hand_right: absolute(self.hand_right)?,
+            center: absolute(self.center)?,
+            shadow: absolute(self.shadow)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RelativeFrameOffset;
+    use crate::FrameOffset;
+
+    fn offsets() -> FrameOffset {
+        FrameOffset {
+            head: (4, 2),
+            hand_left: (2, 4),
+            hand_right: (6, 4),
+            center: (4, 5),
+            shadow: (4, 7),
+        }
+    }
+
+    #[test]
+    fn relative_to_middle_round_trips() {
+        for frame_size in [(8, 8), (9, 8), (8, 11), (32, 40)] {
+            let relative = offsets().relative_to_middle(frame_size);
+            assert_eq!(relative.to_absolute_in_frame(frame_size), Some(offsets()));
+        }
+        let relative = offsets().relative_to_middle((8, 8));
+        assert_eq!(relative.head, (0, -2));
+        assert_eq!(relative.shadow, (0, 3));
+        // Growing the frame by an even amount keep the offsets around the middle
+        assert_eq!(
+            relative.to_absolute_in_frame((12, 10)),
+            offsets().translated((2, 1))
+        );
+    }
+
+    #[test]
+    fn padding_leaves_relative_to_center_unchanged() {
+        let relative = offsets().relative_to_center();
+        assert_eq!(relative.center, (0, 0));
+        assert_eq!(
+            relative,
+            RelativeFrameOffset {
+                head: (0, -3),
+                hand_left: (-2, -1),
+                hand_right: (2, -1),
+                center: (0, 0),
+                shadow: (0, 2),
+            }
+        );
+        for delta in [(0, 0), (3, 0), (0, 7), (10, 20)] {
+            let padded = offsets().translated(delta).unwrap();
+            assert_eq!(padded.relative_to_center(), relative);
+        }
+        assert_eq!(relative.to_absolute((4, 5)), Some(offsets()));
+    }
+
+    #[test]
+    fn out_of_range_offsets_are_refused() {
+        // The hand left would be at x = -1
+        assert_eq!(offsets().translated((-3, 0)), None);
+        assert_eq!(offsets().translated((0, i32::MAX)), None);
+        assert!(offsets().translated((-2, -2)).is_some());
+
+        let relative = offsets().relative_to_center();
+        assert_eq!(relative.to_absolute((1, 5)), None);
+        assert_eq!(relative.to_absolute((4, 65534)), None);
+        assert!(relative.to_absolute((2, 3)).is_some());
+
+        // The shadow is 3 pixels under the middle, outside of a 4×4 frame
+        let relative = offsets().relative_to_middle((8, 8));
+        assert_eq!(relative.to_absolute_in_frame((4, 4)), None);
+        assert_eq!(relative.to_absolute_in_frame((8, 6)), None);
+        assert!(relative.to_absolute_in_frame((5, 8)).is_some());
+    }
+}
@@ -0,0 +1,90 @@
+use crate::SpriteBotStorageError;
+
+/// How the size of the frames of a sheet is chosen, see [`SheetLayoutOptions`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FrameSizeRule {
+    /// The smallest size fitting every frame image and offset of the animation
+    #[default]
+    Fit,
+    /// Like [`FrameSizeRule::Fit`], rounded up to a multiple of the given number (like 8) on each axis. A multiple of 0 doesn’t round, like 1.
+    RoundToMultiple(u32),
+    /// Like [`FrameSizeRule::Fit`], rounded up to a power of two on each axis
+    RoundToPowerOfTwo,
+    /// Always use this width and height. Generating the sheet fail if a frame doesn’t fit in it.
+    Fixed(u32, u32),
+}
+
+/// How the frames are placed in the sheets generated by [`crate::Animation::generate_sheet_with_layout`]
+///
+/// The default is the layout SpriteBot expects: frames fitting their content, at least 8×8, edge to edge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SheetLayoutOptions {
+    pub frame_size: FrameSizeRule,
+    /// The minimum width and height of a frame, before rounding. Ignored with [`FrameSizeRule::Fixed`].
+    pub min_frame_size: (u32, u32),
+    /// Transparent pixels added on each side of the content of every frame, counted in the frame size. The offsets are moved with the image.
+    pub padding: u32,
+    /// Transparent pixels between two frames of the sheet, on both axis.
+    ///
+    /// AnimData.xml has no way to describe it, so writing a sprite with a gutter fail. This is only useful for sheets used by other engines.
+    pub gutter: u32,
+}
+
+impl Default for SheetLayoutOptions {
+    fn default() -> Self {
+        Self {
+            frame_size: FrameSizeRule::Fit,
+            min_frame_size: (8, 8),
+            padding: 0,
+            gutter: 0,
+        }
+    }
+}
+
+impl SheetLayoutOptions {
+    /// The frame size for an animation whose images and offsets need `content_size` pixels
+    pub(crate) fn frame_size(
+        &self,
+        content_size: (u32, u32),
+        animation: &str,
+    ) -> Result<(u32, u32), SpriteBotStorageError> {
+        let to_u32 = |size: u64| -> Result<u32, SpriteBotStorageError> {
+            size.try_into()
+                .map_err(|source| SpriteBotStorageError::TooLargeGeneratedSheet {
+                    animation: animation.to_string(),
+                    source,
+                })
+        };
+        let padded = (
+            to_u32(content_size.0 as u64 + 2 * self.padding as u64)?,
+            to_u32(content_size.1 as u64 + 2 * self.padding as u64)?,
+        );
+        if let FrameSizeRule::Fixed(width, height) = self.frame_size {
+            if padded.0 > width || padded.1 > height {
+                return Err(SpriteBotStorageError::FrameSizeTooSmall {
+                    animation: animation.to_string(),
+                    needed: padded,
+                    frame_size: (width, height),
+                });
+            }
+            return Ok((width, height));
+        }
+
+        let round = |size: u32| -> Result<u32, SpriteBotStorageError> {
+            let size = size as u64;
+            to_u32(match self.frame_size {
+                FrameSizeRule::RoundToMultiple(multiple) => {
+                    // 0 is documented as not rounding
+                    let multiple = multiple.max(1) as u64;
+                    size.div_ceil(multiple) * multiple
+                }
+                FrameSizeRule::RoundToPowerOfTwo => size.next_power_of_two(),
+                _ => size,
+            })
+        };
+        Ok((
+            round(padded.0.max(self.min_frame_size.0))?,
+            round(padded.1.max(self.min_frame_size.1))?,
+        ))
+    }
+}
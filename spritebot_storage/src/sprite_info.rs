@@ -0,0 +1,172 @@
+use std::io::BufReader;
+
+use image::{ImageFormat, ImageReader};
+use vfs::VfsPath;
+
+use crate::{
+    check_sheet_dimensions, folder::RootFolder, folder::SpriteFolder, read_anim_fields,
+    read_animdata, resolve_copy_of, sheet_file_name, AnimDataXML, AnimationKind, ImageKind,
+    SpriteBotStorageError,
+};
+
+/// The metadata of a sprite, read from AnimData.xml without decoding any image.
+///
+/// Useful to list and search sprites, where [`crate::Sprite::new`] would be much slower.
+#[derive(Debug, Clone)]
+pub struct SpriteInfo {
+    pub shadow_size: u8,
+    pub animations: Vec<AnimationInfo>,
+}
+
+/// The metadata of an animation, see [`SpriteInfo`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnimationInfo {
+    pub name: String,
+    pub index: u32,
+    /// The name of the animation this one is a copy of, if any. The other fields are the ones of the copied animation, like with [`crate::Animation::copy_of`].
+    pub copy_of: Option<String>,
+    pub rush_frame: Option<u32>,
+    pub hit_frame: Option<u32>,
+    pub return_frame: Option<u32>,
+    /// The width and height of a frame in the sheets
+    pub frame_size: (u32, u32),
+    /// The duration of each frame, in game ticks
+    pub durations: Vec<u32>,
+    /// The number of rows of frames in the sheets. Only known when the sheets are checked, like with [`SpriteInfo::new_checked`].
+    pub direction_count: Option<u32>,
+}
+
+impl AnimationInfo {
+    pub fn kind(&self) -> AnimationKind {
+        AnimationKind::from_name(&self.name)
+    }
+
+    /// The number of frames in each direction
+    pub fn frame_count(&self) -> usize {
+        self.durations.len()
+    }
+
+    /// The total duration of the animation, in ticks
+    pub fn total_duration(&self) -> u64 {
+        self.durations.iter().map(|duration| *duration as u64).sum()
+    }
+}
+
+impl SpriteInfo {
+    /// Read the metadata of the sprite at the root of the given virtual file system. Only AnimData.xml is read.
+    ///
+    /// `CopyOf` are resolved, and fail like with [`crate::Sprite::new`].
+    pub fn new<T: vfs::FileSystem>(vfs: &T) -> Result<Self, SpriteBotStorageError> {
+        Self::load(&RootFolder(vfs), false)
+    }
+
+    /// Read the metadata of the sprite in the given folder, like [`SpriteInfo::new`]
+    pub fn new_from_path(path: &VfsPath) -> Result<Self, SpriteBotStorageError> {
+        Self::load(path, false)
+    }
+
+    /// Read the metadata like [`SpriteInfo::new`], and also read the header of every sheet to check its dimensions match the frame size and the number of durations, without decoding the pixels.
+    ///
+    /// This fill [`AnimationInfo::direction_count`]. The offsets aren’t checked, so [`crate::Sprite::new`] may still fail.
+    pub fn new_checked<T: vfs::FileSystem>(vfs: &T) -> Result<Self, SpriteBotStorageError> {
+        Self::load(&RootFolder(vfs), true)
+    }
+
+    /// Read and check the metadata of the sprite in the given folder, like [`SpriteInfo::new_checked`]
+    pub fn new_checked_from_path(path: &VfsPath) -> Result<Self, SpriteBotStorageError> {
+        Self::load(path, true)
+    }
+
+    /// Get the animation of the given kind, if this sprite has it
+    pub fn animation(&self, kind: AnimationKind) -> Option<&AnimationInfo> {
+        self.animations
+            .iter()
+            .find(|animation| animation.name == kind.name())
+    }
+
+    pub(crate) fn load<F: SpriteFolder>(
+        folder: &F,
+        check_sheets: bool,
+    ) -> Result<Self, SpriteBotStorageError> {
+        let animdata_xml = read_animdata(folder)?;
+        Self::from_animdata(&animdata_xml, check_sheets.then_some(folder))
+    }
+
+    /// Get the metadata from the content of AnimData.xml, checking the sheets in `folder` if any
+    pub(crate) fn from_animdata<F: SpriteFolder>(
+        animdata_xml: &AnimDataXML,
+        folder: Option<&F>,
+    ) -> Result<Self, SpriteBotStorageError> {
+        let anim_sources = &animdata_xml.anims.anim;
+
+        let mut animations = Vec::new();
+        for anim_source in anim_sources {
+            if anim_source.copy_of.is_some() {
+                animations.push(None);
+                continue;
+            }
+            let (frame_size, durations) = read_anim_fields(anim_source)?;
+            let direction_count = if let Some(folder) = folder {
+                let mut dimensions = [(0, 0); 3];
+                for (kind, dimensions) in ImageKind::ALL.iter().zip(&mut dimensions) {
+                    *dimensions = read_sheet_dimensions(folder, &anim_source.name, *kind)?;
+                }
+                let (_, direction_count) = check_sheet_dimensions(
+                    &anim_source.name,
+                    frame_size,
+                    durations.len(),
+                    dimensions,
+                )?;
+                Some(direction_count)
+            } else {
+                None
+            };
+            animations.push(Some(AnimationInfo {
+                name: anim_source.name.clone(),
+                index: anim_source.index,
+                copy_of: None,
+                rush_frame: anim_source.rush_frame,
+                hit_frame: anim_source.hit_frame,
+                return_frame: anim_source.return_frame,
+                frame_size,
+                durations,
+                direction_count,
+            }));
+        }
+
+        for (anim_nb, anim_source) in anim_sources.iter().enumerate() {
+            if let Some(copy_of) = &anim_source.copy_of {
+                let target_nb = resolve_copy_of(anim_sources, anim_nb)?;
+                // resolve_copy_of always return a non-CopyOf animation, and those were all read above
+                let target = animations[target_nb].as_ref().unwrap();
+                animations[anim_nb] = Some(AnimationInfo {
+                    name: anim_source.name.clone(),
+                    index: anim_source.index,
+                    copy_of: Some(copy_of.clone()),
+                    ..target.clone()
+                });
+            }
+        }
+
+        Ok(Self {
+            shadow_size: animdata_xml.shadow_size,
+            animations: animations.into_iter().map(Option::unwrap).collect(),
+        })
+    }
+}
+
+/// Read the dimensions of a sheet from the header of its PNG file
+fn read_sheet_dimensions<F: SpriteFolder + ?Sized>(
+    folder: &F,
+    name: &str,
+    kind: ImageKind,
+) -> Result<(u32, u32), SpriteBotStorageError> {
+    let file = folder.open_file(&sheet_file_name(name, kind))?;
+    ImageReader::with_format(BufReader::new(file), ImageFormat::Png)
+        .into_dimensions()
+        .map_err(|source| SpriteBotStorageError::ErrorImageRead {
+            animation: name.to_string(),
+            image_kind: kind,
+            source,
+        })
+}
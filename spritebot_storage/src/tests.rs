@@ -0,0 +1,1162 @@
+use std::{
+    io::{Cursor, Read, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use image::{GenericImageView, ImageFormat, Rgba};
+use vfs::{
+    error::VfsErrorKind, FileSystem, MemoryFS, SeekAndRead, SeekAndWrite, VfsMetadata, VfsPath,
+    VfsResult,
+};
+
+use crate::{
+    Animation, AnimationKind, Direction, DirectionLayout, ExtraMetadata, Frame, FrameImage,
+    FrameLocation, FrameOffset, FrameSizeRule, ImageKind, IssueLocation, LazySprite, OffsetKind,
+    RgbaU8, SheetLayoutOptions, Sprite, SpriteBotStorageError, SpriteInfo, ValidationProblem,
+    ValidationWarning, WriteOptions,
+};
+
+/// An 8×8 frame with a single opaque pixel of the given colour at (1, 1), and offsets around its middle
+pub(crate) fn test_frame(color: u8) -> Frame {
+    let mut image = RgbaU8::new(8, 8);
+    image.put_pixel(1, 1, Rgba([color, color, color, 255]));
+    Frame {
+        image: FrameImage::from(image),
+        offsets: FrameOffset {
+            head: (4, 2),
+            hand_left: (2, 4),
+            hand_right: (6, 4),
+            center: (4, 4),
+            shadow: (4, 6),
+        },
+    }
+}
+
+/// An animation with `direction_count` rows of `durations.len()` frames, each of a different colour starting at `first_color`
+pub(crate) fn test_animation(
+    name: &str,
+    index: u32,
+    direction_count: usize,
+    durations: &[u32],
+    first_color: u8,
+) -> Animation {
+    let frame_count = durations.len();
+    Animation {
+        name: name.to_string(),
+        index,
+        copy_of: None,
+        rush_frame: None,
+        hit_frame: None,
+        return_frame: None,
+        durations: durations.to_vec(),
+        images: (0..direction_count)
+            .map(|direction_nb| {
+                (0..frame_count)
+                    .map(|frame_nb| {
+                        test_frame(first_color + (direction_nb * frame_count + frame_nb) as u8)
+                    })
+                    .collect()
+            })
+            .collect(),
+        extra: ExtraMetadata::default(),
+    }
+}
+
+/// A copy of `target` named `name`, that is a `CopyOf` `copy_of`
+pub(crate) fn copy_of(target: &Animation, name: &str, index: u32, copy_of: &str) -> Animation {
+    Animation {
+        name: name.to_string(),
+        index,
+        copy_of: Some(copy_of.to_string()),
+        ..target.clone()
+    }
+}
+
+pub(crate) fn test_sprite(animations: Vec<Animation>) -> Sprite {
+    Sprite {
+        animations,
+        ..Sprite::new_empty(1)
+    }
+}
+
+pub(crate) fn read_file(fs: &MemoryFS, file_name: &str) -> Vec<u8> {
+    let mut content = Vec::new();
+    fs.open_file(&format!("/{}", file_name))
+        .unwrap()
+        .read_to_end(&mut content)
+        .unwrap();
+    content
+}
+
+pub(crate) fn write_file(fs: &MemoryFS, file_name: &str, content: &[u8]) {
+    fs.create_file(&format!("/{}", file_name))
+        .unwrap()
+        .write_all(content)
+        .unwrap();
+}
+
+/// The name of every file at the root of the file system, sorted
+pub(crate) fn file_names(fs: &MemoryFS) -> Vec<String> {
+    let mut names = fs.read_dir("").unwrap().collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+/// The name and content of every file at the root of the file system
+pub(crate) fn files(fs: &MemoryFS) -> Vec<(String, Vec<u8>)> {
+    file_names(fs)
+        .into_iter()
+        .map(|file_name| {
+            let content = read_file(fs, &file_name);
+            (file_name, content)
+        })
+        .collect()
+}
+
+pub(crate) fn write_and_read(sprite: &Sprite) -> (MemoryFS, Sprite) {
+    let mut fs = MemoryFS::new();
+    sprite.write_to_folder(&mut fs).unwrap();
+    let read = Sprite::new(&fs).unwrap();
+    (fs, read)
+}
+
+/// Decode the image `file_name`, change it with `edit`, and write it back
+pub(crate) fn edit_sheet(fs: &MemoryFS, file_name: &str, edit: impl FnOnce(&mut RgbaU8)) {
+    let mut image = image::load_from_memory(&read_file(fs, file_name))
+        .unwrap()
+        .into_rgba8();
+    edit(&mut image);
+    let mut content = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut content), ImageFormat::Png)
+        .unwrap();
+    write_file(fs, file_name, &content);
+}
+
+/// Replace `from` by `to` in AnimData.xml
+pub(crate) fn edit_animdata(fs: &MemoryFS, from: &str, to: &str) {
+    let animdata = String::from_utf8(read_file(fs, "AnimData.xml")).unwrap();
+    assert!(animdata.contains(from), "{} not in AnimData.xml", from);
+    write_file(fs, "AnimData.xml", animdata.replace(from, to).as_bytes());
+}
+
+const COPY_OF_LOOP_ANIMDATA: &str = r#"<?xml version="1.0" ?>
+<AnimData>
+	<ShadowSize>1</ShadowSize>
+	<Anims>
+		<Anim>
+			<Name>Walk</Name>
+			<Index>0</Index>
+			<CopyOf>Idle</CopyOf>
+		</Anim>
+		<Anim>
+			<Name>Idle</Name>
+			<Index>7</Index>
+			<CopyOf>Walk</CopyOf>
+		</Anim>
+	</Anims>
+</AnimData>
+"#;
+
+#[test]
+fn copy_of_chain_is_resolved() {
+    let idle = test_animation("Idle", 7, 8, &[10, 20], 1);
+    let walk = copy_of(&idle, "Walk", 0, "Idle");
+    let sleep = copy_of(&idle, "Sleep", 5, "Walk");
+    let (fs, read) = write_and_read(&test_sprite(vec![sleep, walk, idle.clone()]));
+
+    assert!(!file_names(&fs).contains(&"Walk-Anim.png".to_string()));
+    assert!(!file_names(&fs).contains(&"Sleep-Anim.png".to_string()));
+    let sleep = &read.animations[0];
+    assert_eq!(sleep.copy_of.as_deref(), Some("Walk"));
+    assert_eq!(sleep.index, 5);
+    assert!(sleep.has_same_content(&idle));
+}
+
+#[test]
+fn copy_of_loop_fails_to_read() {
+    let fs = MemoryFS::new();
+    write_file(&fs, "AnimData.xml", COPY_OF_LOOP_ANIMDATA.as_bytes());
+    assert!(matches!(
+        Sprite::new(&fs),
+        Err(SpriteBotStorageError::CopyOfCycle { .. })
+    ));
+}
+
+#[test]
+fn copy_of_not_found_fails_to_read() {
+    let fs = MemoryFS::new();
+    let animdata = COPY_OF_LOOP_ANIMDATA.replace("<CopyOf>Walk</CopyOf>", "<CopyOf>Sleep</CopyOf>");
+    write_file(&fs, "AnimData.xml", animdata.as_bytes());
+    assert!(matches!(
+        Sprite::new(&fs),
+        Err(SpriteBotStorageError::CopyOfNotFound { copy_of, .. }) if copy_of == "Sleep"
+    ));
+}
+
+#[test]
+fn modified_copy_of_is_written_with_its_own_sheets() {
+    let idle = test_animation("Idle", 7, 8, &[10, 20], 1);
+    let walk = copy_of(&idle, "Walk", 0, "Idle");
+    let (_, mut sprite) = write_and_read(&test_sprite(vec![idle, walk]));
+
+    sprite.animations[1].images[0][0].offsets.center = (5, 5);
+    let (fs, read) = write_and_read(&sprite);
+
+    assert!(file_names(&fs).contains(&"Walk-Anim.png".to_string()));
+    assert_eq!(read.animations[1].copy_of, None);
+    assert_eq!(read.animations[1].images[0][0].offsets.center, (5, 5));
+    assert_eq!(read.animations[0].images[0][0].offsets.center, (4, 4));
+}
+
+#[test]
+fn copy_of_loop_fails_to_write_without_touching_the_folder() {
+    let idle = test_animation("Idle", 7, 8, &[10, 20], 1);
+    let (mut fs, _) = write_and_read(&test_sprite(vec![idle.clone()]));
+    let files = file_names(&fs);
+
+    for sprite in [
+        test_sprite(vec![copy_of(&idle, "Idle", 7, "Idle")]),
+        test_sprite(vec![
+            copy_of(&idle, "Idle", 7, "Walk"),
+            copy_of(&idle, "Walk", 0, "Idle"),
+        ]),
+    ] {
+        assert!(matches!(
+            sprite.write_to_folder(&mut fs),
+            Err(SpriteBotStorageError::CopyOfCycle { .. })
+        ));
+        assert_eq!(file_names(&fs), files);
+    }
+}
+
+#[test]
+fn resaving_a_sprite_is_byte_identical() {
+    let idle = test_animation("Idle", 7, 8, &[10, 20], 1);
+    let walk = copy_of(&idle, "Walk", 0, "Idle");
+    let mut attack = test_animation("Attack", 5, 1, &[2, 3, 4], 50);
+    attack.rush_frame = Some(1);
+    let (fs, read) = write_and_read(&test_sprite(vec![idle, walk, attack]));
+    let (resaved_fs, _) = write_and_read(&read);
+
+    assert_eq!(file_names(&resaved_fs), file_names(&fs));
+    for file_name in file_names(&fs) {
+        assert_eq!(
+            read_file(&resaved_fs, &file_name),
+            read_file(&fs, &file_name),
+            "{}",
+            file_name
+        );
+    }
+    let animdata = String::from_utf8(read_file(&fs, "AnimData.xml")).unwrap();
+    assert!(animdata
+        .starts_with("<?xml version=\"1.0\" ?>\n<AnimData>\n\t<ShadowSize>1</ShadowSize>\n"));
+}
+
+#[test]
+fn retime_refuses_invalid_factors() {
+    let mut animation = test_animation("Idle", 7, 1, &[10, 3], 1);
+    animation.retime(1.5).unwrap();
+    assert_eq!(animation.durations, vec![15, 5]);
+    for factor in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+        assert!(matches!(
+            animation.retime(factor),
+            Err(SpriteBotStorageError::InvalidRetimeFactor { .. })
+        ));
+    }
+    assert_eq!(animation.durations, vec![15, 5]);
+}
+
+/// A [`MemoryFS`] where creating the file `fail_on` fail the first time
+#[derive(Debug)]
+struct FailingFS {
+    fs: MemoryFS,
+    fail_on: String,
+    failed: AtomicBool,
+}
+
+impl FileSystem for FailingFS {
+    fn read_dir(&self, path: &str) -> VfsResult<Box<dyn Iterator<Item = String> + Send>> {
+        self.fs.read_dir(path)
+    }
+
+    fn create_dir(&self, path: &str) -> VfsResult<()> {
+        self.fs.create_dir(path)
+    }
+
+    fn open_file(&self, path: &str) -> VfsResult<Box<dyn SeekAndRead + Send>> {
+        self.fs.open_file(path)
+    }
+
+    fn create_file(&self, path: &str) -> VfsResult<Box<dyn SeekAndWrite + Send>> {
+        if path == self.fail_on && !self.failed.swap(true, Ordering::Relaxed) {
+            return Err(VfsErrorKind::Other("test failure".to_string()).into());
+        }
+        self.fs.create_file(path)
+    }
+
+    fn append_file(&self, path: &str) -> VfsResult<Box<dyn SeekAndWrite + Send>> {
+        self.fs.append_file(path)
+    }
+
+    fn metadata(&self, path: &str) -> VfsResult<VfsMetadata> {
+        self.fs.metadata(path)
+    }
+
+    fn exists(&self, path: &str) -> VfsResult<bool> {
+        self.fs.exists(path)
+    }
+
+    fn remove_file(&self, path: &str) -> VfsResult<()> {
+        self.fs.remove_file(path)
+    }
+
+    fn remove_dir(&self, path: &str) -> VfsResult<()> {
+        self.fs.remove_dir(path)
+    }
+}
+
+#[test]
+fn sheets_of_removed_animations_are_removed() {
+    let idle = test_animation("Idle", 7, 8, &[10, 20], 1);
+    let walk = test_animation("Walk", 0, 8, &[4, 4], 100);
+    let (mut fs, _) = write_and_read(&test_sprite(vec![idle.clone(), walk]));
+    write_file(&fs, "credits.txt", b"Someone");
+
+    test_sprite(vec![idle]).write_to_folder(&mut fs).unwrap();
+    assert_eq!(
+        file_names(&fs),
+        vec![
+            "AnimData.xml",
+            "Idle-Anim.png",
+            "Idle-Offsets.png",
+            "Idle-Shadow.png",
+            "credits.txt",
+        ]
+    );
+}
+
+#[test]
+fn failed_write_leaves_the_folder_unchanged() {
+    let idle = test_animation("Idle", 7, 8, &[10, 20], 1);
+    let walk = test_animation("Walk", 0, 8, &[4, 4], 100);
+    let (fs, _) = write_and_read(&test_sprite(vec![idle.clone(), walk]));
+    let before = files(&fs);
+    let changed = test_sprite(vec![test_animation("Idle", 7, 8, &[10, 20], 50)]);
+
+    // While writing the temporary files, then while moving them in place
+    for fail_on in ["/.Idle-Shadow.png.tmp", "/Idle-Shadow.png"] {
+        let mut failing = FailingFS {
+            fs: MemoryFS::new(),
+            fail_on: fail_on.to_string(),
+            failed: AtomicBool::new(false),
+        };
+        for (file_name, content) in &before {
+            write_file(&failing.fs, file_name, content);
+        }
+        assert!(changed.write_to_folder(&mut failing).is_err());
+        assert!(failing.failed.load(Ordering::Relaxed));
+        assert_eq!(files(&failing.fs), before, "{}", fail_on);
+    }
+}
+
+fn incremental() -> WriteOptions {
+    WriteOptions {
+        incremental: true,
+        ..WriteOptions::default()
+    }
+}
+
+/// A copy of the files of `fs` where creating `fail_on` fail
+fn failing_copy(fs: &MemoryFS, fail_on: &str) -> FailingFS {
+    let failing = FailingFS {
+        fs: MemoryFS::new(),
+        fail_on: fail_on.to_string(),
+        failed: AtomicBool::new(false),
+    };
+    for (file_name, content) in files(fs) {
+        write_file(&failing.fs, &file_name, &content);
+    }
+    failing
+}
+
+#[test]
+fn incremental_write_only_writes_modified_animations() {
+    let idle = test_animation("Idle", 7, 8, &[10, 20], 1);
+    let walk = test_animation("Walk", 0, 8, &[4, 4], 100);
+    let (fs, mut sprite) = write_and_read(&test_sprite(vec![idle, walk]));
+    sprite.animations[1].images[0][0].offsets.center = (5, 5);
+    sprite.animations[0].durations = vec![1, 2];
+
+    // Idle-Anim.png is kept, so never created
+    let mut failing = failing_copy(&fs, "/.Idle-Anim.png.tmp");
+    sprite
+        .write_to_folder_with_options(&mut failing, &incremental())
+        .unwrap();
+    assert!(!failing.failed.load(Ordering::Relaxed));
+    assert_eq!(
+        read_file(&failing.fs, "Idle-Anim.png"),
+        read_file(&fs, "Idle-Anim.png")
+    );
+
+    let read = Sprite::new(&failing.fs).unwrap();
+    assert!(read.animations[0].has_same_content(&sprite.animations[0]));
+    assert!(read.animations[1].has_same_content(&sprite.animations[1]));
+}
+
+#[test]
+fn incremental_write_regenerates_sheets_that_changed_in_the_folder() {
+    let idle = test_animation("Idle", 7, 8, &[10, 20], 1);
+    let (fs, sprite) = write_and_read(&test_sprite(vec![idle]));
+
+    // Written in another folder
+    let mut other = MemoryFS::new();
+    sprite
+        .write_to_folder_with_options(&mut other, &incremental())
+        .unwrap();
+    assert_eq!(files(&other), files(&fs));
+
+    // Replaced by another sprite since it was loaded
+    let mut fs = fs;
+    test_sprite(vec![test_animation("Idle", 7, 8, &[10, 20], 50)])
+        .write_to_folder(&mut fs)
+        .unwrap();
+    sprite
+        .write_to_folder_with_options(&mut fs, &incremental())
+        .unwrap();
+    assert_eq!(files(&fs), files(&other));
+}
+
+#[test]
+fn offsets_read_in_sheets_report_the_same_errors_as_in_frames() {
+    let animation = test_animation("Idle", 7, 8, &[10, 20], 1);
+    let ((width, height), _, mut offsets_sheet, mut shadow_sheet) =
+        animation.generate_sheet().unwrap();
+    let transparent = Rgba([0, 0, 0, 0]);
+    // A second centre in direction 1 frame 1, no right hand in direction 2 frame 0, no shadow in direction 3 frame 1, no head in direction 4 frame 0
+    offsets_sheet.put_pixel(width + 5, height + 5, Rgba([0, 255, 0, 255]));
+    offsets_sheet.put_pixel(6, 2 * height + 4, transparent);
+    shadow_sheet.put_pixel(width + 4, 3 * height + 6, transparent);
+    offsets_sheet.put_pixel(4, 4 * height + 2, transparent);
+
+    let read = |direction_nb: u32, frame_nb: u32| {
+        let (x, y) = (frame_nb * width, direction_nb * height);
+        let location = FrameLocation {
+            animation: "Idle",
+            direction: direction_nb as usize,
+            frame: frame_nb as usize,
+            origin: (x, y),
+        };
+        let from_sheets = FrameOffset::from_sheets(
+            &offsets_sheet,
+            &shadow_sheet,
+            (x, y, width, height),
+            &location,
+        );
+        let from_frame = FrameOffset::from_images(
+            &offsets_sheet.view(x, y, width, height).to_image(),
+            &shadow_sheet.view(x, y, width, height).to_image(),
+            direction_nb as usize,
+            frame_nb as usize,
+            "Idle",
+        );
+        assert_eq!(format!("{:?}", from_sheets), format!("{:?}", from_frame));
+        from_sheets
+    };
+    for direction_nb in 0..8 {
+        for frame_nb in 0..2 {
+            let _ = read(direction_nb, frame_nb);
+        }
+    }
+
+    assert_eq!(read(0, 1).unwrap(), test_frame(1).offsets);
+    assert!(matches!(
+        read(1, 1),
+        Err(SpriteBotStorageError::ColorDuplicateInPixelDate { direction: 1, frame: 1, offset: OffsetKind::Center, pixels, .. })
+            if pixels == vec![(width + 4, height + 4), (width + 5, height + 5)]
+    ));
+    assert!(matches!(
+        read(2, 0),
+        Err(SpriteBotStorageError::ColorNotFoundInPixelData {
+            direction: 2,
+            frame: 0,
+            offset: OffsetKind::HandRight,
+            ..
+        })
+    ));
+    assert!(matches!(
+        read(3, 1),
+        Err(SpriteBotStorageError::ColorNotFoundInPixelData {
+            direction: 3,
+            frame: 1,
+            offset: OffsetKind::Shadow,
+            ..
+        })
+    ));
+    // A missing head is at the centre
+    assert_eq!(read(4, 0).unwrap().head, (4, 4));
+}
+
+#[test]
+fn identical_animations_are_deduplicated() {
+    let idle = test_animation("Idle", 7, 8, &[10, 20], 1);
+    let walk = Animation {
+        name: "Walk".to_string(),
+        index: 0,
+        ..idle.clone()
+    };
+    let mut sleep = Animation {
+        name: "Sleep".to_string(),
+        index: 5,
+        ..idle.clone()
+    };
+    sleep.durations = vec![10, 21];
+    let sprite = test_sprite(vec![idle, walk, sleep]);
+
+    let mut fs = MemoryFS::new();
+    sprite
+        .write_to_folder_with_options(
+            &mut fs,
+            &WriteOptions {
+                deduplicate: true,
+                ..WriteOptions::default()
+            },
+        )
+        .unwrap();
+    assert!(!file_names(&fs).contains(&"Walk-Anim.png".to_string()));
+    assert!(file_names(&fs).contains(&"Sleep-Anim.png".to_string()));
+
+    let read = Sprite::new(&fs).unwrap();
+    assert_eq!(read.animations[1].copy_of.as_deref(), Some("Idle"));
+    assert_eq!(read.animations[2].copy_of, None);
+    for (read, animation) in read.animations.iter().zip(&sprite.animations) {
+        assert!(read.has_same_content(animation), "{}", animation.name);
+    }
+}
+
+#[test]
+fn padding_and_cropping_back_keeps_the_frames() {
+    let idle = test_animation("Idle", 7, 8, &[10, 20], 1);
+    let mut padded = idle.clone();
+    padded.pad(1, 2, 3, 4).unwrap();
+    let frame = &padded.images[0][0];
+    assert_eq!(frame.image.dimensions(), (12, 14));
+    assert_eq!(frame.image.get_pixel(2, 3), Rgba([1, 1, 1, 255]));
+    assert_eq!(frame.offsets.center, (5, 6));
+
+    padded.crop(1, 2, 8, 8).unwrap();
+    assert!(padded.has_same_content(&idle));
+
+    assert!(matches!(
+        padded.crop(2, 2, 6, 6),
+        Err(SpriteBotStorageError::ContentClipped {
+            direction: 0,
+            frame: 0,
+            ..
+        })
+    ));
+    assert!(padded.has_same_content(&idle));
+}
+
+#[test]
+fn padding_too_much_is_refused() {
+    let idle = test_animation("Idle", 7, 8, &[10, 20], 1);
+    let mut sprite = test_sprite(vec![idle.clone()]);
+    // The frame would be too wide, or the offsets too far from the left
+    for left in [u32::MAX, 70000] {
+        assert!(matches!(
+            sprite.pad(left, 0, 0, 0),
+            Err(SpriteBotStorageError::CanvasTooLarge {
+                direction: 0,
+                frame: 0,
+                ..
+            })
+        ));
+    }
+    assert!(sprite.animations[0].has_same_content(&idle));
+}
+
+#[test]
+fn trim_keeps_every_pixel_and_offset() {
+    let mut idle = test_animation("Idle", 7, 8, &[10, 20], 1);
+    // Every frame has a pixel at (1, 1) and offsets from (2, 2) to (6, 6), and one also has a pixel at (0, 7)
+    idle.images[3][1]
+        .image
+        .make_mut()
+        .put_pixel(0, 7, Rgba([9, 9, 9, 255]));
+    let mut trimmed = idle.clone();
+    assert_eq!(trimmed.trim(), Some((0, 1, 7, 7)));
+
+    let frame = &trimmed.images[0][0];
+    assert_eq!(frame.image.dimensions(), (7, 7));
+    assert_eq!(frame.image.get_pixel(1, 0), Rgba([1, 1, 1, 255]));
+    assert_eq!(frame.offsets.center, (4, 3));
+    assert_eq!(
+        trimmed.images[3][1].image.get_pixel(0, 6),
+        Rgba([9, 9, 9, 255])
+    );
+
+    trimmed.pad(0, 1, 1, 0).unwrap();
+    assert!(trimmed.has_same_content(&idle));
+
+    let mut empty = test_animation("Idle", 7, 8, &[], 1);
+    assert_eq!(empty.trim(), None);
+}
+
+/// The errors of the validation of the sprite in `fs`, with their location
+fn validation_errors(fs: &MemoryFS) -> Vec<(IssueLocation, SpriteBotStorageError)> {
+    Sprite::validate(fs)
+        .issues
+        .into_iter()
+        .filter_map(|issue| match issue.problem {
+            ValidationProblem::Error(err) => Some((issue.location, err)),
+            ValidationProblem::Warning(_) => None,
+        })
+        .collect()
+}
+
+#[test]
+fn validation_reports_every_marker_problem() {
+    let idle = test_animation("Idle", 7, 8, &[10, 20], 1);
+    let (fs, _) = write_and_read(&test_sprite(vec![idle]));
+    assert!(Sprite::validate(&fs).issues.is_empty());
+
+    // No centre in direction 2 frame 1, and a second one in direction 1 frame 1
+    edit_sheet(&fs, "Idle-Offsets.png", |sheet| {
+        sheet.put_pixel(8 + 4, 2 * 8 + 4, Rgba([0, 0, 0, 0]));
+        sheet.put_pixel(8 + 5, 8 + 5, Rgba([0, 255, 0, 255]));
+    });
+    let errors = validation_errors(&fs);
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+
+    let (location, err) = &errors[0];
+    assert_eq!(
+        *location,
+        IssueLocation {
+            animation: Some("Idle".to_string()),
+            direction: Some(1),
+            frame: Some(1),
+            file: Some("/Idle-Offsets.png".to_string()),
+            pixel: Some((8 + 5, 8 + 5)),
+        }
+    );
+    assert!(matches!(
+        err,
+        SpriteBotStorageError::ColorDuplicateInPixelDate { offset: OffsetKind::Center, pixels, .. }
+            if *pixels == vec![(8 + 4, 8 + 4), (8 + 5, 8 + 5)]
+    ));
+
+    let (location, err) = &errors[1];
+    assert_eq!((location.direction, location.frame), (Some(2), Some(1)));
+    assert!(matches!(
+        err,
+        SpriteBotStorageError::ColorNotFoundInPixelData {
+            offset: OffsetKind::Center,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn validation_reports_sheets_of_different_sizes() {
+    let idle = test_animation("Idle", 7, 8, &[10, 20], 1);
+    let (fs, _) = write_and_read(&test_sprite(vec![idle]));
+    let shadow = image::load_from_memory(&read_file(&fs, "Idle-Shadow.png")).unwrap();
+    edit_sheet(&fs, "Idle-Shadow.png", |sheet| {
+        let mut larger = RgbaU8::new(3 * 8, 8 * 8);
+        image::imageops::replace(&mut larger, &shadow.into_rgba8(), 0, 0);
+        *sheet = larger;
+    });
+
+    let errors = validation_errors(&fs);
+    assert!(errors.iter().any(|(location, err)| matches!(
+        err,
+        SpriteBotStorageError::SpriteSizeNotIdentical {
+            image_kind: ImageKind::Shadow,
+            expected: (16, 64),
+            actual: (24, 64),
+            ..
+        }
+    ) && location.file.as_deref()
+        == Some("/Idle-Shadow.png")));
+    assert!(!Sprite::validate(&fs).is_valid());
+}
+
+#[test]
+fn validation_warns_about_non_canonical_indices() {
+    let idle = test_animation("Idle", 3, 8, &[10, 20], 1);
+    let (fs, _) = write_and_read(&test_sprite(vec![idle]));
+
+    let report = Sprite::validate(&fs);
+    assert!(report.is_valid());
+    let warnings = report.warnings().collect::<Vec<_>>();
+    assert_eq!(warnings.len(), 1);
+    assert!(matches!(
+        warnings[0].problem,
+        ValidationProblem::Warning(ValidationWarning::NonCanonicalIndex {
+            expected: 7,
+            found: 3
+        })
+    ));
+}
+
+#[test]
+fn validation_reports_zero_and_missing_frame_sizes() {
+    let idle = test_animation("Idle", 7, 8, &[10, 20], 1);
+    let (fs, _) = write_and_read(&test_sprite(vec![idle]));
+    let size = "<FrameWidth>8</FrameWidth>\n\t\t\t<FrameHeight>8</FrameHeight>";
+
+    edit_animdata(
+        &fs,
+        size,
+        "<FrameWidth>0</FrameWidth>\n\t\t\t<FrameHeight>0</FrameHeight>",
+    );
+    assert!(matches!(
+        Sprite::new(&fs),
+        Err(SpriteBotStorageError::SpriteSizeZero { .. })
+    ));
+    let errors = validation_errors(&fs);
+    // For each sheet
+    assert_eq!(errors.len(), 3, "{:?}", errors);
+    assert!(errors
+        .iter()
+        .all(|(_, err)| matches!(err, SpriteBotStorageError::SpriteSizeZero { .. })));
+
+    edit_animdata(
+        &fs,
+        "<FrameWidth>0</FrameWidth>\n\t\t\t<FrameHeight>0</FrameHeight>",
+        "",
+    );
+    let fields = validation_errors(&fs)
+        .into_iter()
+        .map(|(_, err)| match err {
+            SpriteBotStorageError::MissingAnimField { field, .. } => field,
+            err => panic!("{:?}", err),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(fields, vec!["FrameWidth", "FrameHeight"]);
+}
+
+#[test]
+fn lenient_read_skips_animations_that_fail_to_load() {
+    let idle = test_animation("Idle", 7, 8, &[10, 20], 1);
+    let attack = test_animation("Attack", 1, 8, &[2, 3], 50);
+    let walk = copy_of(&attack, "Walk", 0, "Attack");
+    let (fs, _) = write_and_read(&test_sprite(vec![attack, idle.clone(), walk]));
+    write_file(&fs, "Attack-Offsets.png", b"not a png");
+
+    let (sprite, skipped) = Sprite::new_lenient(&fs).unwrap();
+    assert_eq!(sprite.animations.len(), 1);
+    assert!(sprite.animations[0].has_same_content(&idle));
+    assert_eq!(skipped.len(), 2);
+    assert_eq!(skipped[0].name, "Attack");
+    assert!(matches!(
+        &skipped[0].error,
+        SpriteBotStorageError::ErrorImageRead {
+            image_kind: ImageKind::Offsets,
+            ..
+        }
+    ));
+    assert_eq!(skipped[1].name, "Walk");
+    assert!(matches!(
+        &skipped[1].error,
+        SpriteBotStorageError::CopyOfSkipped { animation, copy_of }
+            if animation == "Walk" && copy_of == "Attack"
+    ));
+
+    // Strict is still the default
+    assert!(Sprite::new(&fs).is_err());
+}
+
+#[test]
+fn sprite_in_a_sub_folder_round_trips() {
+    let idle = test_animation("Idle", 7, 8, &[10, 20], 1);
+    let sprite = test_sprite(vec![idle.clone()]);
+    let root = VfsPath::new(MemoryFS::new());
+    let folder = root.join("sprite/0025").unwrap();
+    let other = root.join("sprite/0026").unwrap();
+    sprite
+        .write_to_path(&other, &WriteOptions::default())
+        .unwrap();
+
+    sprite
+        .write_to_path(&folder, &WriteOptions::default())
+        .unwrap();
+    assert!(folder.join("AnimData.xml").unwrap().exists().unwrap());
+    assert!(!root.join("AnimData.xml").unwrap().exists().unwrap());
+    let read = Sprite::new_from_path(&folder).unwrap();
+    assert!(read.animations[0].has_same_content(&idle));
+
+    // Removing an animation only remove its sheets in this folder
+    test_sprite(Vec::new())
+        .write_to_path(&folder, &WriteOptions::default())
+        .unwrap();
+    assert!(!folder.join("Idle-Anim.png").unwrap().exists().unwrap());
+    assert!(other.join("Idle-Anim.png").unwrap().exists().unwrap());
+}
+
+#[test]
+fn animdata_is_written_at_the_root() {
+    let (fs, _) = write_and_read(&test_sprite(vec![test_animation("Idle", 7, 8, &[10], 1)]));
+    assert!(fs.exists("/AnimData.xml").unwrap());
+}
+
+#[test]
+fn sprite_info_resolves_copy_of_and_counts_directions() {
+    let idle = test_animation("Idle", 7, 8, &[10, 20], 1);
+    let mut attack = test_animation("Attack", 1, 1, &[2, 3, 4], 50);
+    attack.hit_frame = Some(2);
+    let walk = copy_of(&attack, "Walk", 0, "Attack");
+    let (fs, _) = write_and_read(&test_sprite(vec![idle, walk, attack]));
+
+    let info = SpriteInfo::new(&fs).unwrap();
+    assert!(info
+        .animations
+        .iter()
+        .all(|animation| animation.direction_count.is_none()));
+    let walk = info.animation(AnimationKind::Walk).unwrap();
+    assert_eq!(walk.index, 0);
+    assert_eq!(walk.copy_of.as_deref(), Some("Attack"));
+    assert_eq!(walk.durations, vec![2, 3, 4]);
+    assert_eq!(walk.hit_frame, Some(2));
+    assert_eq!(walk.frame_size, (8, 8));
+
+    let info = SpriteInfo::new_checked(&fs).unwrap();
+    let direction_counts = info
+        .animations
+        .iter()
+        .map(|animation| animation.direction_count)
+        .collect::<Vec<_>>();
+    assert_eq!(direction_counts, vec![Some(8), Some(1), Some(1)]);
+}
+
+#[test]
+fn checked_sprite_info_refuses_sheets_of_the_wrong_size() {
+    let idle = test_animation("Idle", 7, 8, &[10, 20], 1);
+    let (fs, _) = write_and_read(&test_sprite(vec![idle]));
+
+    // Not a multiple of the frame width
+    edit_animdata(
+        &fs,
+        "<FrameWidth>8</FrameWidth>",
+        "<FrameWidth>5</FrameWidth>",
+    );
+    assert!(SpriteInfo::new(&fs).is_ok());
+    assert!(matches!(
+        SpriteInfo::new_checked(&fs),
+        Err(SpriteBotStorageError::SpriteSizeNotMultiple { .. })
+    ));
+
+    // A single frame per row, for two durations
+    edit_animdata(
+        &fs,
+        "<FrameWidth>5</FrameWidth>",
+        "<FrameWidth>16</FrameWidth>",
+    );
+    assert!(matches!(
+        SpriteInfo::new_checked(&fs),
+        Err(SpriteBotStorageError::InconsistantDuration {
+            frame_count: 1,
+            duration_count: 2,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn lazy_sprite_loads_animations_on_access() {
+    let idle = test_animation("Idle", 7, 8, &[10, 20], 1);
+    let attack = test_animation("Attack", 1, 8, &[2, 3], 50);
+    let walk = copy_of(&attack, "Walk", 0, "Attack");
+    let root = VfsPath::new(MemoryFS::new());
+    test_sprite(vec![idle.clone(), walk, attack.clone()])
+        .write_to_path(&root, &WriteOptions::default())
+        .unwrap();
+    let offsets_file = root.join("Attack-Offsets.png").unwrap();
+    let mut offsets = Vec::new();
+    offsets_file
+        .open_file()
+        .unwrap()
+        .read_to_end(&mut offsets)
+        .unwrap();
+    offsets_file
+        .create_file()
+        .unwrap()
+        .write_all(b"not a png")
+        .unwrap();
+
+    let sprite = LazySprite::new_from_path(root).unwrap();
+    assert!(!sprite.is_loaded(AnimationKind::Idle));
+    let loaded = sprite.animation(AnimationKind::Idle).unwrap().unwrap();
+    assert!(loaded.has_same_content(&idle));
+    assert!(sprite.is_loaded(AnimationKind::Idle));
+    assert!(sprite.animation(AnimationKind::Sleep).unwrap().is_none());
+
+    // The CopyOf fail to load its target, until the sheet is fixed
+    assert!(matches!(
+        sprite.animation(AnimationKind::Walk),
+        Err(SpriteBotStorageError::ErrorImageRead { .. })
+    ));
+    assert!(!sprite.is_loaded(AnimationKind::Walk));
+    assert!(!sprite.is_loaded(AnimationKind::Attack));
+    offsets_file
+        .create_file()
+        .unwrap()
+        .write_all(&offsets)
+        .unwrap();
+    let walk = sprite.animation(AnimationKind::Walk).unwrap().unwrap();
+    assert_eq!(walk.copy_of.as_deref(), Some("Attack"));
+    assert!(walk.has_same_content(&attack));
+    assert!(sprite.is_loaded(AnimationKind::Attack));
+}
+
+const CENTER_PIXEL: Rgba<u8> = Rgba([0, 255, 0, 255]);
+
+/// Generate the sheets of an animation of two directions of two frames with the layout, and check the centre of the last frame is at `last_center`
+fn generate_with_layout(layout: SheetLayoutOptions, last_center: (u32, u32)) -> (u32, u32) {
+    let animation = test_animation("Idle", 7, 2, &[1, 2], 1);
+    let (frame_size, anim_sheet, offsets_sheet, _) =
+        animation.generate_sheet_with_layout(&layout).unwrap();
+    assert_eq!(
+        offsets_sheet.get_pixel(last_center.0, last_center.1),
+        &CENTER_PIXEL
+    );
+    // The pixel of the frame is at (1, 1), and the centre at (4, 4)
+    assert_eq!(
+        anim_sheet.get_pixel(last_center.0 - 3, last_center.1 - 3),
+        &Rgba([4, 4, 4, 255])
+    );
+    assert_eq!(anim_sheet.dimensions(), offsets_sheet.dimensions());
+    frame_size
+}
+
+#[test]
+fn sheet_layouts_size_and_place_frames() {
+    let animation = test_animation("Idle", 7, 2, &[1, 2], 1);
+    let sheet_size = |layout: &SheetLayoutOptions| {
+        animation
+            .generate_sheet_with_layout(layout)
+            .unwrap()
+            .1
+            .dimensions()
+    };
+
+    let layout = SheetLayoutOptions::default();
+    assert_eq!(generate_with_layout(layout.clone(), (8 + 4, 8 + 4)), (8, 8));
+    assert_eq!(sheet_size(&layout), (16, 16));
+
+    let layout = SheetLayoutOptions {
+        frame_size: FrameSizeRule::Fixed(10, 12),
+        ..SheetLayoutOptions::default()
+    };
+    assert_eq!(
+        generate_with_layout(layout.clone(), (10 + 4, 12 + 4)),
+        (10, 12)
+    );
+    assert_eq!(sheet_size(&layout), (20, 24));
+
+    // 8 + 2 × 1 of padding, rounded to 16
+    let layout = SheetLayoutOptions {
+        frame_size: FrameSizeRule::RoundToPowerOfTwo,
+        padding: 1,
+        ..SheetLayoutOptions::default()
+    };
+    assert_eq!(
+        generate_with_layout(layout.clone(), (16 + 5, 16 + 5)),
+        (16, 16)
+    );
+    assert_eq!(sheet_size(&layout), (32, 32));
+
+    let layout = SheetLayoutOptions {
+        frame_size: FrameSizeRule::RoundToMultiple(5),
+        min_frame_size: (11, 3),
+        ..SheetLayoutOptions::default()
+    };
+    assert_eq!(generate_with_layout(layout, (15 + 4, 10 + 4)), (15, 10));
+    let layout = SheetLayoutOptions {
+        frame_size: FrameSizeRule::RoundToMultiple(0),
+        ..SheetLayoutOptions::default()
+    };
+    assert_eq!(generate_with_layout(layout, (8 + 4, 8 + 4)), (8, 8));
+
+    // Only between frames
+    let layout = SheetLayoutOptions {
+        gutter: 3,
+        ..SheetLayoutOptions::default()
+    };
+    assert_eq!(
+        generate_with_layout(layout.clone(), (11 + 4, 11 + 4)),
+        (8, 8)
+    );
+    assert_eq!(sheet_size(&layout), (19, 19));
+}
+
+#[test]
+fn sheet_layouts_that_cant_be_used_are_refused() {
+    let animation = test_animation("Idle", 7, 2, &[1, 2], 1);
+    let layout = SheetLayoutOptions {
+        frame_size: FrameSizeRule::Fixed(6, 10),
+        padding: 1,
+        ..SheetLayoutOptions::default()
+    };
+    assert!(matches!(
+        animation.generate_sheet_with_layout(&layout),
+        Err(SpriteBotStorageError::FrameSizeTooSmall {
+            needed: (10, 10),
+            frame_size: (6, 10),
+            ..
+        })
+    ));
+
+    let mut fs = MemoryFS::new();
+    let options = WriteOptions {
+        layout: SheetLayoutOptions {
+            gutter: 1,
+            ..SheetLayoutOptions::default()
+        },
+        ..WriteOptions::default()
+    };
+    assert!(matches!(
+        test_sprite(vec![animation]).write_to_folder_with_options(&mut fs, &options),
+        Err(SpriteBotStorageError::GutterNotSupported)
+    ));
+    assert!(file_names(&fs).is_empty());
+}
+
+#[test]
+fn single_row_is_shared_by_every_direction() {
+    let mut single = test_animation("Sleep", 5, 1, &[10, 20], 1);
+    let mut eight = test_animation("Walk", 0, 8, &[10, 20], 1);
+    let mut three = test_animation("Idle", 7, 3, &[10, 20], 1);
+    assert_eq!(single.direction_layout(), Some(DirectionLayout::Single));
+    assert_eq!(eight.direction_layout(), Some(DirectionLayout::Eight));
+    assert_eq!(three.direction_layout(), None);
+
+    single.frames_mut(Direction::Left).unwrap()[0]
+        .offsets
+        .center = (5, 5);
+    for direction in Direction::ALL {
+        assert_eq!(single.frames(direction).unwrap().len(), 2);
+        assert_eq!(single.frames(direction).unwrap()[0].offsets.center, (5, 5));
+    }
+
+    eight.frames_mut(Direction::Left).unwrap()[0].offsets.center = (5, 5);
+    for direction in Direction::ALL {
+        let expected = if direction == Direction::Left {
+            (5, 5)
+        } else {
+            (4, 4)
+        };
+        assert_eq!(eight.frames(direction).unwrap()[0].offsets.center, expected);
+    }
+
+    // Only the rows that exist can be accessed
+    assert!(three.frames_mut(Direction::Up).is_none());
+    assert!(three.frames(Direction::DownRight).is_some());
+}
+
+#[test]
+fn durations_longer_than_255_ticks_round_trip() {
+    let sprite = test_sprite(vec![test_animation("Idle", 7, 1, &[1000, 70000], 1)]);
+    let (fs, read) = write_and_read(&sprite);
+    assert_eq!(read.animations[0].durations, vec![1000, 70000]);
+    assert_eq!(read.animations[0].total_duration(), 71000);
+    let animdata = String::from_utf8(read_file(&fs, "AnimData.xml")).unwrap();
+    assert!(animdata.contains("<Duration>70000</Duration>"));
+
+    // Only durations that don’t fit in 32 bits are refused
+    edit_animdata(
+        &fs,
+        "<Duration>70000</Duration>",
+        "<Duration>4294967296</Duration>",
+    );
+    assert!(matches!(
+        Sprite::new(&fs),
+        Err(SpriteBotStorageError::TooLargeDuration {
+            frame: 1,
+            duration: 4294967296,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn other_with_a_standard_name_finds_the_standard_animation() {
+    let mut sprite = test_sprite(vec![
+        test_animation("Walk", 0, 8, &[10, 20], 1),
+        test_animation("Cutscene", 40, 1, &[10], 100),
+    ]);
+    let other_walk = AnimationKind::Other("Walk".to_string());
+    assert!(std::ptr::eq(
+        sprite.animation(other_walk.clone()).unwrap(),
+        sprite.animation(AnimationKind::Walk).unwrap()
+    ));
+    assert_eq!(sprite.animation(AnimationKind::Walk).unwrap().name, "Walk");
+    assert_eq!(
+        sprite
+            .animation(AnimationKind::from_name("Cutscene"))
+            .unwrap()
+            .index,
+        40
+    );
+    assert!(sprite.animation(AnimationKind::Idle).is_none());
+
+    sprite.animation_mut(other_walk).unwrap().durations[0] = 30;
+    assert_eq!(
+        sprite.animation(AnimationKind::Walk).unwrap().durations,
+        vec![30, 20]
+    );
+}
+
+#[test]
+fn make_mut_leaves_the_sheet_and_the_other_frames_unchanged() {
+    let mut sheet = RgbaU8::new(16, 8);
+    sheet.put_pixel(1, 1, Rgba([1, 1, 1, 255]));
+    sheet.put_pixel(9, 1, Rgba([2, 2, 2, 255]));
+    let sheet = Arc::new(sheet);
+    let mut first = FrameImage::from_sheet(sheet.clone(), 0, 0, 8, 8);
+    let second = FrameImage::from_sheet(sheet.clone(), 8, 0, 8, 8);
+    let first_copy = first.clone();
+    assert!(first.is_shared() && second.is_shared());
+    assert_eq!(second.get_pixel(1, 1), Rgba([2, 2, 2, 255]));
+
+    first.make_mut().put_pixel(1, 1, Rgba([3, 3, 3, 255]));
+    assert!(!first.is_shared());
+    assert_eq!(first.get_pixel(1, 1), Rgba([3, 3, 3, 255]));
+    assert!(second.is_shared() && first_copy.is_shared());
+    assert_eq!(second.get_pixel(1, 1), Rgba([2, 2, 2, 255]));
+    assert_eq!(first_copy.get_pixel(1, 1), Rgba([1, 1, 1, 255]));
+    assert_eq!(sheet.get_pixel(1, 1), &Rgba([1, 1, 1, 255]));
+    assert_eq!(sheet.get_pixel(9, 1), &Rgba([2, 2, 2, 255]));
+
+    // Same for the frames of a loaded sprite
+    let sprite = test_sprite(vec![test_animation("Walk", 0, 8, &[10, 20], 1)]);
+    let (_, mut read) = write_and_read(&sprite);
+    let walk = &mut read.animations[0];
+    assert!(walk
+        .images
+        .iter()
+        .flatten()
+        .all(|frame| frame.image.is_shared()));
+    walk.images[0][0]
+        .image
+        .make_mut()
+        .put_pixel(1, 1, Rgba([200, 200, 200, 255]));
+    assert_eq!(
+        walk.images[0][0].image.get_pixel(1, 1),
+        Rgba([200, 200, 200, 255])
+    );
+    for (read_row, row) in walk.images.iter().zip(&sprite.animations[0].images) {
+        for (read_frame, frame) in read_row.iter().zip(row).skip(1) {
+            assert!(read_frame.image.is_shared());
+            assert_eq!(read_frame.image, frame.image);
+        }
+    }
+    assert_eq!(
+        walk.images[1][0].image,
+        sprite.animations[0].images[1][0].image
+    );
+}
@@ -0,0 +1,395 @@
+use crate::{
+    animdata_xml::AnimXML, convert_duration, decode_sheet, folder::SpriteFolder,
+    get_number_of_component_on_axis, map_ordered, read_animdata, read_sheet_file, resolve_copy_of,
+    scan_markers, sheet_file_name, AnimationKind, Axis, ImageKind, OffsetMarker, RgbaU8,
+    SpriteBotStorageError, ANIMDATA_FILE_NAME, CENTER_MARKER, HAND_LEFT_MARKER, HAND_RIGHT_MARKER,
+    HEAD_MARKER, SHADOW_MARKER,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The sprite can be read, but something is probably not intended
+    Warning,
+    /// The sprite can’t be read
+    Error,
+}
+
+/// Where a problem was found. Fields are `None` when the problem isn’t specific to them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IssueLocation {
+    pub animation: Option<String>,
+    /// The index of the direction (the row in the sheet), see [`crate::Direction::from_index`]
+    pub direction: Option<usize>,
+    pub frame: Option<usize>,
+    pub file: Option<String>,
+    /// Position of the pixel in the whole sheet (not in the frame)
+    pub pixel: Option<(u32, u32)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ValidationWarning {
+    /// There is no black pixel in the offsets frame, so the head will be placed on the center
+    MissingHeadOffset,
+    /// The index in AnimData.xml isn’t the canonical one for this animation name
+    NonCanonicalIndex { expected: u32, found: u32 },
+}
+
+#[derive(Debug)]
+pub enum ValidationProblem {
+    Error(SpriteBotStorageError),
+    Warning(ValidationWarning),
+}
+
+#[derive(Debug)]
+pub struct ValidationIssue {
+    pub location: IssueLocation,
+    pub problem: ValidationProblem,
+}
+
+impl ValidationIssue {
+    pub fn severity(&self) -> Severity {
+        match self.problem {
+            ValidationProblem::Error(_) => Severity::Error,
+            ValidationProblem::Warning(_) => Severity::Warning,
+        }
+    }
+}
+
+/// All the problems found in a sprite, in the order of the animations, directions and frames
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// Return true if there is no error. There may still be warnings.
+    pub fn is_valid(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity() == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity() == Severity::Warning)
+    }
+
+    fn error(&mut self, location: IssueLocation, error: SpriteBotStorageError) {
+        self.issues.push(ValidationIssue {
+            location,
+            problem: ValidationProblem::Error(error),
+        });
+    }
+
+    fn warning(&mut self, location: IssueLocation, warning: ValidationWarning) {
+        self.issues.push(ValidationIssue {
+            location,
+            problem: ValidationProblem::Warning(warning),
+        });
+    }
+}
+
+pub(crate) fn validate<F: SpriteFolder>(folder: &F) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    let animdata = match read_animdata(folder) {
+        Ok(animdata) => animdata,
+        Err(err) => {
+            let location = IssueLocation {
+                file: Some(folder.file_path(ANIMDATA_FILE_NAME)),
+                ..Default::default()
+            };
+            report.error(location, err);
+            return report;
+        }
+    };
+
+    let animdata_path = folder.file_path(ANIMDATA_FILE_NAME);
+    let anims = &animdata.anims.anim;
+    // The folder is read in order, and the animations are checked in parallel with the `rayon` feature
+    let jobs = anims
+        .iter()
+        .enumerate()
+        .map(|(anim_nb, anim)| (anim_nb, anim, read_sheet_files(folder, anim)))
+        .collect();
+    let anim_reports = map_ordered(jobs, |(anim_nb, anim, sheet_files)| {
+        let mut report = ValidationReport::default();
+        let location = IssueLocation {
+            animation: Some(anim.name.clone()),
+            ..Default::default()
+        };
+        if let Some(expected) = AnimationKind::from_name(&anim.name).index() {
+            if expected != anim.index {
+                report.warning(
+                    IssueLocation {
+                        file: Some(animdata_path.clone()),
+                        ..location.clone()
+                    },
+                    ValidationWarning::NonCanonicalIndex {
+                        expected,
+                        found: anim.index,
+                    },
+                );
+            }
+        }
+        if anim.copy_of.is_some() {
+            if let Err(err) = resolve_copy_of(anims, anim_nb) {
+                report.error(location, err);
+            }
+        } else {
+            validate_animation(anim, &animdata_path, sheet_files, location, &mut report);
+        }
+        report
+    });
+    for anim_report in anim_reports {
+        report.issues.extend(anim_report.issues);
+    }
+    report
+}
+
+/// A sheet file of an animation, with its path in the folder
+struct SheetFile {
+    kind: ImageKind,
+    path: String,
+    content: Result<Vec<u8>, SpriteBotStorageError>,
+}
+
+/// Read the sheets of the animation, if it isn’t a `CopyOf` and AnimData.xml has enough information to check them
+fn read_sheet_files<F: SpriteFolder>(folder: &F, anim: &AnimXML) -> Vec<SheetFile> {
+    if anim.copy_of.is_some()
+        || anim.frame_width.is_none()
+        || anim.frame_height.is_none()
+        || anim.durations.is_none()
+    {
+        return Vec::new();
+    }
+    ImageKind::ALL
+        .iter()
+        .map(|kind| SheetFile {
+            kind: *kind,
+            path: folder.file_path(&sheet_file_name(&anim.name, *kind)),
+            content: read_sheet_file(folder, &anim.name, *kind),
+        })
+        .collect()
+}
+
+/// A sheet of an animation that could be read, and whose size is a multiple of the frame size
+struct CheckedSheet {
+    kind: ImageKind,
+    path: String,
+    image: RgbaU8,
+    /// The number of frames on each row and the number of rows
+    frame_count: (u32, u32),
+}
+
+fn validate_animation(
+    anim: &AnimXML,
+    animdata_path: &str,
+    sheet_files: Vec<SheetFile>,
+    location: IssueLocation,
+    report: &mut ValidationReport,
+) {
+    let missing_field = |field| SpriteBotStorageError::MissingAnimField {
+        animation: anim.name.clone(),
+        field,
+    };
+    let frame_size = match (anim.frame_width, anim.frame_height) {
+        (Some(width), Some(height)) => Some((width, height)),
+        (width, height) => {
+            if width.is_none() {
+                report.error(location.clone(), missing_field("FrameWidth"));
+            }
+            if height.is_none() {
+                report.error(location.clone(), missing_field("FrameHeight"));
+            }
+            None
+        }
+    };
+    let durations = match &anim.durations {
+        Some(durations) => &durations.duration,
+        None => {
+            report.error(location.clone(), missing_field("Durations"));
+            return;
+        }
+    };
+    for (frame_nb, duration) in durations.iter().enumerate() {
+        if let Err(err) = convert_duration(&anim.name, frame_nb, *duration) {
+            let location = IssueLocation {
+                frame: Some(frame_nb),
+                file: Some(animdata_path.to_string()),
+                ..location.clone()
+            };
+            report.error(location, err);
+        }
+    }
+    // A frame size of zero is reported for each sheet below
+    let frame_size = match frame_size {
+        Some(frame_size) => frame_size,
+        None => return,
+    };
+
+    let mut sheets = Vec::new();
+    for sheet_file in sheet_files {
+        let kind = sheet_file.kind;
+        let sheet_location = IssueLocation {
+            file: Some(sheet_file.path.clone()),
+            ..location.clone()
+        };
+        let image = sheet_file
+            .content
+            .and_then(|content| decode_sheet(&content, &anim.name, kind));
+        let checked = image.and_then(|image| {
+            let nb_on_width = get_number_of_component_on_axis(
+                image.width(),
+                frame_size.0,
+                Axis::Width,
+                &anim.name,
+                kind,
+            )?;
+            let nb_on_height = get_number_of_component_on_axis(
+                image.height(),
+                frame_size.1,
+                Axis::Height,
+                &anim.name,
+                kind,
+            )?;
+            Ok(CheckedSheet {
+                kind,
+                path: sheet_file.path,
+                image,
+                frame_count: (nb_on_width, nb_on_height),
+            })
+        });
+        match checked {
+            Ok(sheet) => {
+                if sheet.frame_count.0 as usize != durations.len() {
+                    report.error(
+                        sheet_location,
+                        SpriteBotStorageError::InconsistantDuration {
+                            animation: anim.name.clone(),
+                            frame_count: sheet.frame_count.0 as usize,
+                            duration_count: durations.len(),
+                        },
+                    );
+                }
+                sheets.push(sheet);
+            }
+            Err(err) => report.error(sheet_location, err),
+        }
+    }
+
+    if let Some(first) = sheets.first() {
+        let mismatched = sheets
+            .iter()
+            .filter(|sheet| sheet.image.dimensions() != first.image.dimensions())
+            .collect::<Vec<_>>();
+        for sheet in &mismatched {
+            report.error(
+                IssueLocation {
+                    file: Some(sheet.path.clone()),
+                    ..location.clone()
+                },
+                SpriteBotStorageError::SpriteSizeNotIdentical {
+                    animation: anim.name.clone(),
+                    image_kind: sheet.kind,
+                    expected: first.image.dimensions(),
+                    actual: sheet.image.dimensions(),
+                },
+            );
+        }
+        if !mismatched.is_empty() {
+            return;
+        }
+    }
+
+    let offsets_sheet = sheets.iter().find(|sheet| sheet.kind == ImageKind::Offsets);
+    let shadow_sheet = sheets.iter().find(|sheet| sheet.kind == ImageKind::Shadow);
+    for (sheet, markers) in [
+        (
+            offsets_sheet,
+            &[
+                HEAD_MARKER,
+                HAND_LEFT_MARKER,
+                CENTER_MARKER,
+                HAND_RIGHT_MARKER,
+            ][..],
+        ),
+        (shadow_sheet, &[SHADOW_MARKER][..]),
+    ] {
+        if let Some(sheet) = sheet {
+            validate_markers(anim, sheet, frame_size, markers, &location, report);
+        }
+    }
+}
+
+/// Check every frame of the sheet has exactly one pixel for each marker
+fn validate_markers(
+    anim: &AnimXML,
+    sheet: &CheckedSheet,
+    frame_size: (u32, u32),
+    markers: &[OffsetMarker],
+    location: &IssueLocation,
+    report: &mut ValidationReport,
+) {
+    for direction_nb in 0..sheet.frame_count.1 {
+        for frame_nb in 0..sheet.frame_count.0 {
+            let frame_start = (frame_nb * frame_size.0, direction_nb * frame_size.1);
+            let found = scan_markers(
+                &sheet.image,
+                (frame_start.0, frame_start.1, frame_size.0, frame_size.1),
+                markers,
+            );
+            let frame_location = IssueLocation {
+                direction: Some(direction_nb as usize),
+                frame: Some(frame_nb as usize),
+                file: Some(sheet.path.clone()),
+                ..location.clone()
+            };
+            for (marker, pixels) in markers.iter().zip(found) {
+                if pixels.is_empty() {
+                    if marker.optional {
+                        report
+                            .warning(frame_location.clone(), ValidationWarning::MissingHeadOffset);
+                    } else {
+                        report.error(
+                            frame_location.clone(),
+                            SpriteBotStorageError::ColorNotFoundInPixelData {
+                                animation: anim.name.clone(),
+                                direction: direction_nb as usize,
+                                frame: frame_nb as usize,
+                                image_kind: sheet.kind,
+                                offset: marker.offset,
+                            },
+                        );
+                    }
+                }
+                let pixels = pixels
+                    .iter()
+                    .map(|pixel| (frame_start.0 + pixel.0, frame_start.1 + pixel.1))
+                    .collect::<Vec<_>>();
+                // Report every pixel after the first one, so all of them can be located
+                for pixel in pixels.iter().skip(1) {
+                    report.error(
+                        IssueLocation {
+                            pixel: Some(*pixel),
+                            ..frame_location.clone()
+                        },
+                        SpriteBotStorageError::ColorDuplicateInPixelDate {
+                            animation: anim.name.clone(),
+                            direction: direction_nb as usize,
+                            frame: frame_nb as usize,
+                            image_kind: sheet.kind,
+                            offset: marker.offset,
+                            pixels: pixels.clone(),
+                        },
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,285 @@
+#[cfg(feature = "async")]
+use std::future::Future;
+
+use crate::{folder::SpriteFolder, is_sheet_file_name, GeneratedFile, SpriteBotStorageError};
+
+/// A change to a file of the folder of a sprite, see [`WritePlan`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum FileOperation<'a> {
+    /// Create (or replace) the file with the given content
+    Write {
+        file_name: String,
+        content: &'a [u8],
+    },
+    /// Move the file `from` to `to`, which doesn’t exist
+    Move {
+        from: String,
+        to: String,
+    },
+    Remove {
+        file_name: String,
+    },
+}
+
+impl FileOperation<'_> {
+    /// The operations that undo this one once it is done
+    fn undo(&self) -> Vec<Self> {
+        match self {
+            Self::Write { file_name, .. } => vec![Self::Remove {
+                file_name: file_name.clone(),
+            }],
+            Self::Move { from, to } => vec![
+                // A failed move to `from` may have left a partial copy there
+                Self::Remove {
+                    file_name: from.clone(),
+                },
+                Self::Move {
+                    from: to.clone(),
+                    to: from.clone(),
+                },
+            ],
+            // Only backups left by an interrupted write are removed before the commit, see WritePlan::new
+            Self::Remove { .. } => Vec::new(),
+        }
+    }
+}
+
+fn temp_file_name(file_name: &str) -> String {
+    format!(".{}.tmp", file_name)
+}
+
+fn backup_file_name(file_name: &str) -> String {
+    format!(".{}.bak", file_name)
+}
+
+/// The operations replacing the files of a sprite in a folder with the generated ones, so a failure doesn’t leave a mix of old and new files:
+///
+/// 1. Every changed file is written to a temporary file.
+/// 2. The files that will be replaced are moved to a backup file.
+/// 3. The temporary files are moved to their final name. This is the only step where the folder doesn’t contain a complete sprite.
+/// 4. The backups, and the sheets of animations that aren’t in the sprite anymore, are removed.
+///
+/// If an operation of the first three steps fail, the operations already done are undone, so the folder is left as it was (except for a possible partial copy when a file system without move fail in the middle of one). A failure in the last step leave the new sprite complete.
+///
+/// The plan is run with [`WritePlan::run`], or with [`WritePlan::run_async`] for async file systems.
+pub(crate) struct WritePlan<'a> {
+    pub operations: Vec<FileOperation<'a>>,
+    /// The number of operations that are undone if one of them fail
+    commit_end: usize,
+}
+
+impl<'a> WritePlan<'a> {
+    /// Plan the write of `files` in a folder that currently contain `existing_files`
+    pub fn new(files: &'a [GeneratedFile], existing_files: &[String]) -> Self {
+        let exists = |file_name: &String| existing_files.contains(file_name);
+        let changed_files = files
+            .iter()
+            .filter_map(|file| Some((&file.name, file.content.as_deref()?)))
+            .collect::<Vec<_>>();
+
+        let mut operations = Vec::new();
+        for (file_name, content) in &changed_files {
+            operations.push(FileOperation::Write {
+                file_name: temp_file_name(file_name),
+                content,
+            });
+        }
+        // Backups to remove once the new files are in place
+        let mut backups = Vec::new();
+        for (file_name, _) in &changed_files {
+            let backup = backup_file_name(file_name);
+            if exists(file_name) {
+                if exists(&backup) {
+                    // Left by an interrupted write, while the file was restored or written since
+                    operations.push(FileOperation::Remove {
+                        file_name: backup.clone(),
+                    });
+                }
+                operations.push(FileOperation::Move {
+                    from: file_name.to_string(),
+                    to: backup.clone(),
+                });
+                backups.push(backup);
+            } else if exists(&backup) {
+                backups.push(backup);
+            }
+        }
+        for (file_name, _) in &changed_files {
+            operations.push(FileOperation::Move {
+                from: temp_file_name(file_name),
+                to: file_name.to_string(),
+            });
+        }
+        let commit_end = operations.len();
+
+        for backup in backups {
+            operations.push(FileOperation::Remove { file_name: backup });
+        }
+        for file_name in existing_files {
+            if is_sheet_file_name(file_name) && !files.iter().any(|file| &file.name == file_name) {
+                operations.push(FileOperation::Remove {
+                    file_name: file_name.clone(),
+                });
+            }
+        }
+        Self {
+            operations,
+            commit_end,
+        }
+    }
+
+    /// The operations to run, in order, after the operation `failed` failed. They are best effort: their errors should be ignored, as the original error is more useful.
+    pub fn rollback(&self, failed: usize) -> Vec<FileOperation<'a>> {
+        if failed >= self.commit_end {
+            return Vec::new();
+        }
+        let mut rollback = Vec::new();
+        // Unlike a move, a failed write may still have created the file
+        if let FileOperation::Write { file_name, .. } = &self.operations[failed] {
+            rollback.push(FileOperation::Remove {
+                file_name: file_name.clone(),
+            });
+        }
+        for operation in self.operations[..failed].iter().rev() {
+            rollback.extend(operation.undo());
+        }
+        rollback
+    }
+
+    /// Run the plan in the folder, undoing it if it fails
+    pub fn run<F: SpriteFolder + ?Sized>(&self, folder: &F) -> Result<(), SpriteBotStorageError> {
+        for (operation_nb, operation) in self.operations.iter().enumerate() {
+            if let Err(err) = folder.apply(operation) {
+                for operation in self.rollback(operation_nb) {
+                    let _ = folder.apply(&operation);
+                }
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Like [`WritePlan::run`], with `apply` running an operation in an async file system
+    #[cfg(feature = "async")]
+    pub async fn run_async<A, R>(&self, apply: A) -> Result<(), SpriteBotStorageError>
+    where
+        A: Fn(FileOperation<'a>) -> R,
+        R: Future<Output = Result<(), SpriteBotStorageError>>,
+    {
+        for (operation_nb, operation) in self.operations.iter().enumerate() {
+            if let Err(err) = apply(operation.clone()).await {
+                for operation in self.rollback(operation_nb) {
+                    let _ = apply(operation).await;
+                }
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn remove(file_name: &str) -> FileOperation<'static> {
+        FileOperation::Remove {
+            file_name: file_name.to_string(),
+        }
+    }
+
+    fn move_file(from: &str, to: &str) -> FileOperation<'static> {
+        FileOperation::Move {
+            from: from.to_string(),
+            to: to.to_string(),
+        }
+    }
+
+    #[test]
+    fn failed_commit_is_undone() {
+        let files = vec![
+            GeneratedFile {
+                name: "Idle-Anim.png".to_string(),
+                content: Some(b"new".to_vec()),
+            },
+            GeneratedFile {
+                name: "AnimData.xml".to_string(),
+                content: Some(b"new".to_vec()),
+            },
+        ];
+        let existing_files = ["AnimData.xml", "Idle-Anim.png", "Walk-Anim.png"].map(String::from);
+        let plan = WritePlan::new(&files, &existing_files);
+
+        let write = |file_name: &str| FileOperation::Write {
+            file_name: file_name.to_string(),
+            content: b"new",
+        };
+        assert_eq!(
+            plan.operations,
+            vec![
+                write(".Idle-Anim.png.tmp"),
+                write(".AnimData.xml.tmp"),
+                move_file("Idle-Anim.png", ".Idle-Anim.png.bak"),
+                move_file("AnimData.xml", ".AnimData.xml.bak"),
+                move_file(".Idle-Anim.png.tmp", "Idle-Anim.png"),
+                move_file(".AnimData.xml.tmp", "AnimData.xml"),
+                remove(".Idle-Anim.png.bak"),
+                remove(".AnimData.xml.bak"),
+                remove("Walk-Anim.png"),
+            ]
+        );
+
+        // Moving AnimData.xml in place failed
+        assert_eq!(
+            plan.rollback(5),
+            vec![
+                remove(".Idle-Anim.png.tmp"),
+                move_file("Idle-Anim.png", ".Idle-Anim.png.tmp"),
+                remove("AnimData.xml"),
+                move_file(".AnimData.xml.bak", "AnimData.xml"),
+                remove("Idle-Anim.png"),
+                move_file(".Idle-Anim.png.bak", "Idle-Anim.png"),
+                remove(".AnimData.xml.tmp"),
+                remove(".Idle-Anim.png.tmp"),
+            ]
+        );
+        // The new sprite is complete once every file is in place
+        assert_eq!(plan.rollback(8), Vec::new());
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn failed_async_commit_is_undone() {
+        use std::sync::Mutex;
+
+        let files = vec![GeneratedFile {
+            name: "Idle-Anim.png".to_string(),
+            content: Some(b"new".to_vec()),
+        }];
+        let existing_files = ["Idle-Anim.png".to_string()];
+        let plan = WritePlan::new(&files, &existing_files);
+        // Moving the new file in place fail
+        let failing = move_file(".Idle-Anim.png.tmp", "Idle-Anim.png");
+
+        let applied = Mutex::new(Vec::new());
+        let result = async_std::task::block_on(plan.run_async(|operation| {
+            let result = if operation == failing {
+                Err(SpriteBotStorageError::GutterNotSupported)
+            } else {
+                Ok(())
+            };
+            applied.lock().unwrap().push(operation);
+            async { result }
+        }));
+        assert!(result.is_err());
+
+        let failed = plan
+            .operations
+            .iter()
+            .position(|operation| *operation == failing);
+        let mut expected = plan.operations[..=failed.unwrap()].to_vec();
+        expected.extend(plan.rollback(failed.unwrap()));
+        assert_eq!(applied.into_inner().unwrap(), expected);
+        assert!(expected.contains(&move_file(".Idle-Anim.png.bak", "Idle-Anim.png")));
+    }
+}
@@ -0,0 +1,183 @@
+use std::{
+    cell::RefCell,
+    io::{self, Cursor, Read, Seek, Write},
+};
+
+use vfs::{error::VfsErrorKind, SeekAndRead, SeekAndWrite, VfsResult};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{
+    folder::SpriteFolder, SkippedAnimation, Sprite, SpriteBotStorageError, SpriteInfo,
+    WriteOptions, ANIMDATA_FILE_NAME,
+};
+
+/// The folder of a zip archive containing AnimData.xml
+struct ZipFolder<R> {
+    archive: RefCell<ZipArchive<R>>,
+    /// The folder in the archive, empty or ending with a `/`
+    prefix: String,
+}
+
+impl<R: Read + Seek> ZipFolder<R> {
+    /// Open the archive. The sprite is either at the root of the archive, or in the least nested folder containing an AnimData.xml.
+    fn new(reader: R) -> Result<Self, SpriteBotStorageError> {
+        let archive = ZipArchive::new(reader)?;
+        let prefix = archive
+            .file_names()
+            .filter_map(|name| name.strip_suffix(ANIMDATA_FILE_NAME))
+            .filter(|prefix| prefix.is_empty() || prefix.ends_with('/'))
+            .min_by_key(|prefix| prefix.matches('/').count())
+            .unwrap_or_default()
+            .to_string();
+        Ok(Self {
+            archive: RefCell::new(archive),
+            prefix,
+        })
+    }
+}
+
+impl<R: Read + Seek> SpriteFolder for ZipFolder<R> {
+    fn file_path(&self, file_name: &str) -> String {
+        format!("{}{}", self.prefix, file_name)
+    }
+
+    fn open_vfs_file(&self, file_name: &str) -> VfsResult<Box<dyn SeekAndRead + Send>> {
+        let mut archive = self.archive.borrow_mut();
+        let mut file = archive
+            .by_name(&self.file_path(file_name))
+            .map_err(io::Error::from)?;
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)?;
+        Ok(Box::new(Cursor::new(content)))
+    }
+
+    fn create_vfs_file(&self, _file_name: &str) -> VfsResult<Box<dyn SeekAndWrite + Send>> {
+        Err(VfsErrorKind::NotSupported.into())
+    }
+
+    fn create_folder(&self) -> VfsResult<()> {
+        Err(VfsErrorKind::NotSupported.into())
+    }
+
+    fn list_vfs_files(&self) -> VfsResult<Vec<String>> {
+        let archive = self.archive.borrow();
+        Ok(archive
+            .file_names()
+            .filter_map(|name| name.strip_prefix(&self.prefix))
+            .filter(|name| !name.contains('/'))
+            .map(|name| name.to_string())
+            .collect())
+    }
+
+    fn remove_vfs_file(&self, _file_name: &str) -> VfsResult<()> {
+        Err(VfsErrorKind::NotSupported.into())
+    }
+
+    fn move_vfs_file(&self, _from: &str, _to: &str) -> VfsResult<()> {
+        Err(VfsErrorKind::NotSupported.into())
+    }
+}
+
+impl Sprite {
+    /// Read the sprite contained in a zip archive, like the ones given by SpriteBot, like [`Sprite::new`]
+    pub fn new_from_zip<R: Read + Seek>(reader: R) -> Result<Self, SpriteBotStorageError> {
+        Self::load(&ZipFolder::new(reader)?, false).map(|(sprite, _)| sprite)
+    }
+
+    /// Read the sprite contained in a zip archive, like [`Sprite::new_lenient`]
+    pub fn new_lenient_from_zip<R: Read + Seek>(
+        reader: R,
+    ) -> Result<(Self, Vec<SkippedAnimation>), SpriteBotStorageError> {
+        Self::load(&ZipFolder::new(reader)?, true)
+    }
+
+    /// Write the sprite as a zip archive that can be submitted to SpriteBot, with every file at the root of the archive.
+    ///
+    /// Return the writer once the archive is complete.
+    pub fn write_to_zip<W: Write + Seek>(
+        &self,
+        writer: W,
+        options: &WriteOptions,
+    ) -> Result<W, SpriteBotStorageError> {
+        let files = self.generate_files(options, None)?;
+        let mut zip = ZipWriter::new(writer);
+        let file_options =
+            SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        // Without a folder, every file has a content
+        for (file_name, content) in files
+            .into_iter()
+            .filter_map(|file| Some((file.name, file.content?)))
+        {
+            zip.start_file(&file_name, file_options)?;
+            zip.write_all(&content)
+                .map_err(|source| SpriteBotStorageError::WriteFileError {
+                    source,
+                    path: file_name,
+                })?;
+        }
+        Ok(zip.finish()?)
+    }
+}
+
+impl SpriteInfo {
+    /// Read the metadata of the sprite contained in a zip archive, like [`SpriteInfo::new`]
+    pub fn new_from_zip<R: Read + Seek>(reader: R) -> Result<Self, SpriteBotStorageError> {
+        Self::load(&ZipFolder::new(reader)?, false)
+    }
+
+    /// Read and check the metadata of the sprite contained in a zip archive, like [`SpriteInfo::new_checked`]
+    pub fn new_checked_from_zip<R: Read + Seek>(reader: R) -> Result<Self, SpriteBotStorageError> {
+        Self::load(&ZipFolder::new(reader)?, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{test_animation, test_sprite};
+
+    #[test]
+    fn zip_round_trip() {
+        let idle = test_animation("Idle", 7, 8, &[10, 20], 1);
+        let zip = test_sprite(vec![idle.clone()])
+            .write_to_zip(Cursor::new(Vec::new()), &WriteOptions::default())
+            .unwrap();
+
+        let read = Sprite::new_from_zip(Cursor::new(zip.into_inner())).unwrap();
+        assert_eq!(read.animations.len(), 1);
+        assert!(read.animations[0].has_same_content(&idle));
+    }
+
+    #[test]
+    fn sprite_is_read_in_the_least_nested_folder() {
+        let idle = test_animation("Idle", 7, 8, &[10, 20], 1);
+        let zip = test_sprite(vec![idle.clone()])
+            .write_to_zip(Cursor::new(Vec::new()), &WriteOptions::default())
+            .unwrap();
+        let mut sprite_files = ZipArchive::new(Cursor::new(zip.into_inner())).unwrap();
+
+        // The sprite in a folder, and an older broken copy in a sub-folder of it
+        let mut nested = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        nested
+            .start_file("Sprites/0025/old/AnimData.xml", options)
+            .unwrap();
+        nested.write_all(b"not xml").unwrap();
+        for file_nb in 0..sprite_files.len() {
+            let mut file = sprite_files.by_index(file_nb).unwrap();
+            let mut content = Vec::new();
+            file.read_to_end(&mut content).unwrap();
+            nested
+                .start_file(format!("Sprites/0025/{}", file.name()), options)
+                .unwrap();
+            nested.write_all(&content).unwrap();
+        }
+        nested.start_file("credits.txt", options).unwrap();
+        let nested = nested.finish().unwrap().into_inner();
+
+        let folder = ZipFolder::new(Cursor::new(nested.clone())).unwrap();
+        assert_eq!(folder.prefix, "Sprites/0025/");
+        let read = Sprite::new_from_zip(Cursor::new(nested)).unwrap();
+        assert!(read.animations[0].has_same_content(&idle));
+    }
+}